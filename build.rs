@@ -0,0 +1,37 @@
+//! Embeds the API data snapshot in `snapshot/` if present, see
+//! `snapshot/README.md`. Without it, an empty snapshot is embedded, which is
+//! never loaded, unless `PESCAN_REQUIRE_SNAPSHOT` is set for release builds.
+
+use std::{env, fs};
+use std::path::Path;
+
+fn main() {
+  println!("cargo::rerun-if-changed=snapshot");
+  println!("cargo::rerun-if-env-changed=PESCAN_REQUIRE_SNAPSHOT");
+
+  let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+  let snapshot = Path::new("snapshot/data.mpk");
+  let out = Path::new(&out_dir).join("snapshot.mpk");
+
+  if snapshot.is_file() {
+    fs::copy(snapshot, &out).expect("could not copy snapshot");
+
+    let date = fs::read_to_string("snapshot/date")
+      .map(|date| date.trim().to_owned())
+      .unwrap_or_else(|_| String::from("unknown date"));
+
+    println!("cargo::rustc-env=PESCAN_SNAPSHOT_DATE={date}");
+  } else {
+    if env::var_os("PESCAN_REQUIRE_SNAPSHOT").is_some() {
+      panic!("snapshot/data.mpk is missing but PESCAN_REQUIRE_SNAPSHOT is set, see snapshot/README.md");
+    }
+
+    println!(
+      "cargo::warning=snapshot/data.mpk is missing, the binary will have no \
+      fallback without network access, see snapshot/README.md"
+    );
+
+    // empty snapshot is never loaded
+    fs::write(&out, []).expect("could not write empty snapshot");
+  }
+}
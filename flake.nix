@@ -49,6 +49,8 @@
         default = naerskOverride.buildPackage {
           src = ./.;
           strictDeps = true;
+
+          PESCAN_REQUIRE_SNAPSHOT = "1";
         };
 
         dockerImage = pkgs.dockerTools.buildLayeredImage {
@@ -75,6 +77,7 @@
         ];
 
         CARGO_BUILD_TARGET = "x86_64-pc-windows-gnu";
+        PESCAN_REQUIRE_SNAPSHOT = "1";
       };
     };
   };
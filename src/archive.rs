@@ -0,0 +1,108 @@
+//! Provides [Kind] enum for detecting ZIP and 7z archives and
+//! [for_each_member] function for reading their files into memory one at a
+//! time without extracting to disk.
+
+use anyhow::{Result, Context};
+use sevenz_rust::{Password, SevenZReader};
+use zip::ZipArchive;
+
+use std::io::{self, Cursor, Read};
+
+/// Conventional password of malware sample archives
+pub const DEFAULT_PASSWORD: &str = "infected";
+
+/// Supported archive formats
+pub enum Kind {
+  /// ZIP archive, optionally encrypted with ZipCrypto or AES
+  Zip,
+  /// 7z archive, optionally encrypted with AES
+  SevenZ,
+}
+
+impl Kind {
+  /// Detects archive format from magic bytes at the start of `buffer`
+  pub fn detect(buffer: &[u8]) -> Option<Kind> {
+    if buffer.starts_with(b"PK\x03\x04") {
+      Some(Kind::Zip)
+    } else if buffer.starts_with(b"7z\xBC\xAF\x27\x1C") {
+      Some(Kind::SevenZ)
+    } else {
+      None
+    }
+  }
+}
+
+/// Maximum decompressed size of an archive member which is scanned
+pub const MAX_MEMBER_SIZE: u64 = 256 * 1024 * 1024;
+/// Maximum number of archives nested in one another which are unpacked
+pub const MAX_DEPTH: usize = 4;
+
+/// File stored in an archive
+pub struct Member {
+  /// Path of file inside archive
+  pub name: String,
+  /// Decompressed contents of file, [None] if larger than [MAX_MEMBER_SIZE]
+  pub data: Option<Vec<u8>>,
+}
+
+/// Reads member from `reader`, unless it is larger than [MAX_MEMBER_SIZE]
+fn read_member(reader: &mut dyn Read) -> std::io::Result<Option<Vec<u8>>> {
+  let mut data = Vec::new();
+  reader.take(MAX_MEMBER_SIZE + 1).read_to_end(&mut data)?;
+
+  Ok((data.len() as u64 <= MAX_MEMBER_SIZE).then_some(data))
+}
+
+/// Reads files in archive `buffer` one at a time, decrypting with
+/// `password` where needed, and passes each to `on_member` before reading
+/// the next
+pub fn for_each_member<F>(buffer: &[u8], kind: Kind, password: &str, mut on_member: F) -> Result<()>
+where
+  F: FnMut(Member) -> Result<()>
+{
+  match kind {
+    Kind::Zip => {
+      let mut archive = ZipArchive::new(Cursor::new(buffer))?;
+
+      for i in 0..archive.len() {
+        let mut file = archive.by_index_decrypt(i, password.as_bytes())
+          .with_context(|| format!("could not decrypt archive member {i}"))?;
+
+        if !file.is_file() {
+          continue;
+        }
+
+        let name = file.name().to_owned();
+        let data = read_member(&mut file)?;
+
+        on_member(Member { name, data })?;
+      }
+    },
+    Kind::SevenZ => {
+      let mut archive = SevenZReader::new(
+        Cursor::new(buffer),
+        buffer.len().try_into()?,
+        Password::from(password)
+      )?;
+      let mut result = Ok(());
+
+      archive.for_each_entries(|entry, reader| {
+        if entry.is_directory() || !entry.has_stream() {
+          return Ok(true);
+        }
+
+        let data = read_member(reader)?;
+        // the rest of a skipped member must be consumed before the next one
+        io::copy(reader, &mut io::sink())?;
+
+        result = on_member(Member { name: entry.name().to_owned(), data });
+
+        Ok(result.is_ok())
+      })?;
+
+      result?;
+    },
+  }
+
+  Ok(())
+}
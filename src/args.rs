@@ -1,22 +1,70 @@
 //! Provides [Args] struct with [clap] derive syntax for defining
 //! CLI interface
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use camino::Utf8PathBuf;
 
-use crate::output::Format;
+use std::num::NonZeroUsize;
+
+use pescan::archive;
+use pescan::cache::{StalePolicy, PortableFormat, SourceSpec};
+use pescan::input::CollectOptions;
+use pescan::output::{Format, SampleOptions};
+use pescan::score::Weight;
+use pescan::exit::FailOn;
 
 /// pescan - static analysis tool for PE files via API import analysis
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-  /// Sample File
+  /// Subcommand run instead of scanning samples
+  #[command(subcommand)]
+  pub command: Option<Command>,
+
+  /// Sample files or directories, read from stdin if empty
   #[arg(value_name="FILE")]
-  pub sample: Option<Utf8PathBuf>,
+  pub sample: Vec<Utf8PathBuf>,
+  /// Scan directories recursively
+  #[arg(short, long)]
+  pub recursive: bool,
+  /// Only scan files in directories matching glob, can be repeated
+  #[arg(long, value_name="GLOB")]
+  pub include: Vec<String>,
+  /// Skip files in directories matching glob, can be repeated
+  #[arg(long, value_name="GLOB")]
+  pub exclude: Vec<String>,
+  /// Password of ZIP and 7z sample archives
+  #[arg(long, default_value=archive::DEFAULT_PASSWORD)]
+  pub password: String,
+  /// Number of samples to scan in parallel [default: number of CPUs]
+  #[arg(short, long, value_name="N")]
+  pub jobs: Option<NonZeroUsize>,
 
-  /// Update cache
+  /// Update cache (requires the `scrape` feature)
   #[arg(short, long)]
   pub update: bool,
+  /// Source of API data for cache updates, such as a local mirror
+  /// [default: source_url in config.toml, or https://malapi.io]
+  #[arg(long, value_name="URL", env="PESCAN_SOURCE_URL")]
+  pub source_url: Option<String>,
+  /// Maximum age of the cache in days, 0 to never expire
+  /// [default: max_age in config.toml, or 90]
+  #[arg(long, value_name="DAYS")]
+  pub max_age: Option<u64>,
+  /// Action taken when the cache is older than --max-age
+  /// [default: stale in config.toml, or warn]
+  #[arg(long, value_name="POLICY")]
+  pub stale: Option<StalePolicy>,
+  /// Additional source of API definitions, can be repeated. KIND is
+  /// `attack` for CSV or JSON lists mapping APIs to ATT&CK techniques, or
+  /// `capa` for directories of capa rules
+  #[arg(long, value_name="KIND=PATH")]
+  pub source: Vec<SourceSpec>,
+  /// Overlay of local API definitions (JSON or YAML, as exported by
+  /// `pescan cache export`) merged on top of the cache
+  /// [default: overlay in config.toml, or overlay.yaml in config directory]
+  #[arg(long, value_name="PATH", env="PESCAN_OVERLAY")]
+  pub overlay: Option<Utf8PathBuf>,
 
   /// Show summary of API functionality
   #[arg(short, long)]
@@ -27,10 +75,39 @@ pub struct Args {
   /// Show link to documentation of API
   #[arg(short, long)]
   pub documentation: bool,
-  /// Alias for -ild
+  /// Show sources of API data
+  #[arg(short, long)]
+  pub sources: bool,
+  /// Alias for -ilds
   #[arg(short='A', long)]
   pub all: bool,
 
+  /// Show potential attack chain
+  #[arg(short, long)]
+  pub chain: bool,
+
+  /// Match API names exactly, without ignoring A/W and Ex suffixes or
+  /// treating Zw and Nt prefixes as equal
+  #[arg(long)]
+  pub exact: bool,
+  /// Do not match strings of samples against API names to find APIs
+  /// resolved at runtime
+  #[arg(long)]
+  pub no_strings: bool,
+
+  /// Behaviour rule file (YAML, TOML or JSON), can be repeated
+  #[arg(long, value_name="PATH")]
+  pub rules: Vec<Utf8PathBuf>,
+
+  /// Weight of technique category in score, can be repeated
+  #[arg(long, value_name="CATEGORY=WEIGHT")]
+  pub weight: Vec<Weight>,
+
+  /// Exit with code 3 if any API in CATEGORY matched or score is at
+  /// least SCORE, can be repeated. Parse and cache failures exit with 4 and 5
+  #[arg(long, value_name="CATEGORY|SCORE")]
+  pub fail_on: Vec<FailOn>,
+
   /// Maximum width of tables
   #[arg(short, long, default_value_t=80)]
   pub width: usize,
@@ -42,3 +119,60 @@ pub struct Args {
   #[arg(short='o', long="output", value_name="PATH")]
   pub path: Option<Utf8PathBuf>,
 }
+
+impl Args {
+  /// Details of samples selected using `-i`, `-l`, `-d`, `-s`, `-A` and
+  /// `-c` flags
+  pub fn sample_options(&self) -> SampleOptions {
+    SampleOptions {
+      info: self.info || self.all,
+      library: self.library || self.all,
+      documentation: self.documentation || self.all,
+      sources: self.sources || self.all,
+      chain: self.chain,
+    }
+  }
+
+  /// Selection of files in directories set using `--recursive`, `--include`
+  /// and `--exclude`
+  pub fn collect_options(&self) -> CollectOptions {
+    CollectOptions {
+      recursive: self.recursive,
+      include: self.include.clone(),
+      exclude: self.exclude.clone(),
+    }
+  }
+}
+
+/// Subcommands of [Args]
+#[derive(Subcommand)]
+pub enum Command {
+  /// Manage the API cache
+  Cache {
+    /// Cache action
+    #[command(subcommand)]
+    action: CacheAction,
+  },
+}
+
+/// Actions of `pescan cache`
+#[derive(Subcommand)]
+pub enum CacheAction {
+  /// Export cache as JSON or YAML for review or editing
+  Export {
+    /// Export format [default: from output extension, or json]
+    #[arg(short, long, value_enum)]
+    format: Option<PortableFormat>,
+    /// Output path, stdout if not given
+    #[arg(short='o', long="output", value_name="PATH")]
+    path: Option<Utf8PathBuf>,
+  },
+  /// Replace cache with an exported JSON or YAML file
+  Import {
+    /// Import format [default: from file extension, or json]
+    #[arg(short, long, value_enum)]
+    format: Option<PortableFormat>,
+    /// Exported cache file
+    path: Utf8PathBuf,
+  },
+}
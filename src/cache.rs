@@ -1,27 +1,95 @@
-//! Provides [Cache] struct for loading and creating a cache of APIs
-
-use serde::{Serialize, Deserialize};
-use anyhow::{Result, Context, anyhow};
+//! Provides [Cache] struct for loading and creating a cache of APIs.
+//! Creating and updating the cache requires the `scrape` feature. If no
+//! cache exists, a snapshot embedded at build time is used instead.
+
+use serde::{Serialize, Serializer, Deserialize};
+use clap::ValueEnum;
+use anyhow::{Result, Context, bail};
+#[cfg(feature = "scrape")]
+use anyhow::anyhow;
+#[cfg(feature = "scrape")]
 use scraper::{Selector, Html};
-use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "scrape")]
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+#[cfg(feature = "scrape")]
+use tokio::{sync::{Mutex, Semaphore}, task::JoinSet, time::{self, MissedTickBehavior}};
 
 use std::collections::HashSet;
+#[cfg(feature = "scrape")]
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::fs::{self, File};
-use std::time::Duration;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "scrape")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+mod schema;
+mod portable;
+mod overlay;
+mod source;
+
+pub use schema::{FileHeader, SCHEMA_VERSION};
+pub use portable::{Portable, PortableCategory, PortableFormat};
+pub use source::{Source, SourceKind, SourceSpec, TechniqueMap, CapaRules, MALAPI, LOCAL};
+
+/// Snapshot of cache embedded at build time, empty if none was available
+const SNAPSHOT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/snapshot.mpk"));
+/// Date on which [SNAPSHOT] was scraped
+pub const SNAPSHOT_DATE: Option<&str> = option_env!("PESCAN_SNAPSHOT_DATE");
+
+/// Default source of API data, mirrors must serve the same pages
+pub const DEFAULT_SOURCE_URL: &str = "https://malapi.io";
+
+/// Default maximum age of the cache in days, see [StalePolicy]
+pub const DEFAULT_MAX_AGE: u64 = 90;
+
+/// Maximum number of detail pages fetched at once
+#[cfg(feature = "scrape")]
+const CONCURRENCY: usize = 8;
+/// Maximum number of detail pages requested per second
+#[cfg(feature = "scrape")]
+const REQUESTS_PER_SECOND: u32 = 10;
+/// Maximum number of attempts to fetch a page before giving up
+#[cfg(feature = "scrape")]
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled after every further attempt
+#[cfg(feature = "scrape")]
+const BACKOFF: Duration = Duration::from_millis(500);
+/// Maximum time to establish a connection before retrying
+#[cfg(feature = "scrape")]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum time for a whole request, including reading the page, before
+/// retrying
+#[cfg(feature = "scrape")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Wrapper for API data for caching purposes
-#[derive(Default)]
+#[derive(Clone, Default)]
 #[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Api {
   name: String,
 
   /// Summary of API functionality
+  #[serde(default)]
   pub info: String,
   /// DLL which API originated
+  #[serde(default)]
   pub library: String,
   /// Link to documentation web page
+  #[serde(default)]
   pub documentation: String,
+  /// Reason details could not be scraped, if they are missing
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+  /// MITRE ATT&CK technique IDs of API, from sources mapping APIs to them
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub techniques: Vec<String>,
+  /// Names of the sources API came from, see [Source]
+  #[serde(skip)]
+  pub sources: Vec<String>,
 }
 
 impl PartialEq for Api {
@@ -38,27 +106,131 @@ impl Hash for Api {
   }
 }
 
+/// Action taken when the cache is older than its maximum age
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StalePolicy {
+  /// Warn and use the cache anyway
+  #[default]
+  Warn,
+  /// Use the cache while updating it for future runs alongside the scan,
+  /// waiting for the update to finish before exiting
+  Refresh,
+  /// Fail until the cache is updated
+  Refuse,
+}
+
+/// Options of [Cache::load]
+#[derive(Clone)]
+pub struct LoadOptions {
+  /// Update cache even if it exists
+  pub update: bool,
+  /// Source of API data for updates
+  pub source_url: String,
+  /// Hide progress and summaries of updates
+  pub quiet: bool,
+  /// Additional sources of API definitions merged on top of the cache
+  pub sources: Vec<SourceSpec>,
+  /// Overlay of local API definitions merged on top of the cache and sources
+  pub overlay: Option<PathBuf>,
+}
+
+impl Default for LoadOptions {
+  fn default() -> LoadOptions {
+    LoadOptions {
+      update: false,
+      source_url: String::from(DEFAULT_SOURCE_URL),
+      quiet: false,
+      sources: Vec::new(),
+      overlay: None,
+    }
+  }
+}
+
 /// Wrapper around APIs and headers for caching purposes
 #[derive(Default)]
 #[derive(Serialize, Deserialize)]
 pub struct Cache {
   /// `Vec<String>` containing malware behaviour categories
   pub headers: Vec<String>,
-  apis: Vec<HashSet<Api>>
+  #[serde(serialize_with = "serialize_sorted")]
+  apis: Vec<HashSet<Api>>,
+  /// Time of the last update, in seconds since the Unix epoch
+  #[serde(default)]
+  pub scraped: Option<u64>,
+  /// Date of embedded snapshot, if cache was loaded from it
+  #[serde(skip)]
+  pub snapshot: Option<String>,
+  /// Header of the file cache was loaded from or saved to
+  #[serde(skip)]
+  pub file_header: Option<FileHeader>,
+  /// Names and credits of the sources merged into the cache
+  #[serde(skip)]
+  attributions: Vec<(String, String)>,
+}
+
+/// Changes made to the cache by [Cache::update]
+#[derive(Default)]
+pub struct Diff {
+  /// APIs which were not cached, with their categories
+  pub added: Vec<(String, Vec<String>)>,
+  /// APIs which are no longer listed, with their former categories
+  pub removed: Vec<(String, Vec<String>)>,
+  /// APIs listed in different categories, with their former and current categories
+  pub moved: Vec<(String, Vec<String>, Vec<String>)>,
+}
+
+impl Diff {
+  /// Whether no APIs were added, removed or moved
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+  }
+}
+
+impl fmt::Display for Diff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "{} API(s) added, {} removed and {} moved:",
+      self.added.len(), self.removed.len(), self.moved.len())?;
+
+    for (name, categories) in &self.added {
+      writeln!(f, "  + {name} ({})", categories.join(", "))?;
+    }
+    for (name, categories) in &self.removed {
+      writeln!(f, "  - {name} ({})", categories.join(", "))?;
+    }
+    for (name, former, current) in &self.moved {
+      writeln!(f, "  ~ {name} ({} -> {})", former.join(", "), current.join(", "))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Serializes categories of APIs sorted by name, so that caches with the same
+/// contents are identical
+fn serialize_sorted<S>(apis: &[HashSet<Api>], serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+  S: Serializer
+{
+  serializer.collect_seq(apis.iter().map(|category| {
+    let mut category = category.iter().collect::<Vec<_>>();
+    category.sort_by(|a, b| a.name.cmp(&b.name));
+    category
+  }))
 }
 
 impl Cache {
+  #[cfg(feature = "scrape")]
   /// Scrape headers from index page
-  fn scrape_headers(&mut self, document: &Html) -> Result<()> {
+  fn scrape_headers(document: &Html) -> Result<Vec<String>> {
     let header_selector = Selector::parse("th")
       .map_err(|e| anyhow!("failed to parse selector: {e}"))?;
 
-    self.headers = document.select(&header_selector)
-      .map(|element| element.text().map(|s| s.trim()).collect()).collect();
-
-    Ok(())
+    Ok(document.select(&header_selector)
+      .map(|element| element.text().map(|s| s.trim()).collect()).collect())
   }
 
+  #[cfg(feature = "scrape")]
   /// Scrape API list from index page (for individual detail fetching)
   fn scrape_apis(document: &Html) -> Result<Vec<Vec<String>>> {
     let mut apis: Vec<Vec<String>> = Vec::new();
@@ -77,126 +249,460 @@ impl Cache {
     Ok(apis)
   }
 
-  /// Fetch details for all apis from <https://malapi.io> for caching
+  #[cfg(feature = "scrape")]
+  /// Scrape details of API `name` from its page
+  fn scrape_details(name: &str, page: &str, details_selector: &Selector) -> Result<Api> {
+    let document = Html::parse_document(page);
+
+    let content = document.select(details_selector)
+      .collect::<Vec<_>>();
+
+    let info = content.get(1)
+      .context("could not find info")?
+      .text().collect::<String>().trim().to_string();
+
+    let library = content.get(2)
+      .context("could not find library")?
+      .text().collect::<String>().trim().to_string();
+
+    let documentation = content.get(4)
+      .context("could not find documentation")?
+      .text().collect::<String>().trim().to_string();
+
+    Ok(Api {
+      name: name.to_owned(),
+      info,
+      library,
+      documentation,
+      error: None,
+      techniques: Vec::new(),
+      sources: Vec::new(),
+    })
+  }
+
+  #[cfg(feature = "scrape")]
+  /// Fetch page at `url` once `limiter` allows, retrying network errors,
+  /// timeouts, server errors and rate limiting with exponential backoff
+  async fn fetch(client: &reqwest::Client, url: &str, limiter: &Mutex<time::Interval>) -> Result<String> {
+    let mut attempt = 1;
+
+    loop {
+      limiter.lock().await.tick().await;
+
+      let error = match client.get(url).send().await.and_then(|res| res.error_for_status()) {
+        Ok(res) => match res.text().await {
+          Ok(page) => return Ok(page),
+          Err(e) => e,
+        },
+        Err(e) => e,
+      };
+
+      let transient = error.status().is_none_or(|status| {
+        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+      });
+
+      if !transient || attempt == MAX_ATTEMPTS {
+        return Err(error).with_context(|| format!("failed after {attempt} attempt(s)"));
+      }
+
+      time::sleep(BACKOFF * 2u32.pow(attempt - 1)).await;
+      attempt += 1;
+    }
+  }
+
+  #[cfg(feature = "scrape")]
+  /// Re-scrape the index at `source_url` and fetch details from
+  /// `{source_url}/winapi/` of APIs which are new or whose details are
+  /// missing, removing APIs which are no longer listed. Up to [CONCURRENCY]
+  /// pages are fetched at once, at no more than [REQUESTS_PER_SECOND]. APIs
+  /// whose details could not be scraped are kept with their [Api::error].
+  /// Changes and failures are listed once all pages are done, unless `quiet`.
   pub async fn update(
-    &mut self
-  ) -> Result<()> {
+    &mut self,
+    source_url: &str,
+    quiet: bool
+  ) -> Result<Diff> {
     let client = reqwest::Client::builder()
       .user_agent(format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")))
       .cookie_store(false)
+      .connect_timeout(CONNECT_TIMEOUT)
+      .timeout(REQUEST_TIMEOUT)
       .build()?;
 
-    let details_url = String::from("https://malapi.io/winapi/");
+    let source_url = source_url.trim_end_matches('/');
+    let details_url = format!("{source_url}/winapi/");
     let details_selector = Selector::parse(".content")
       .map_err(|e| anyhow!("failed to parse selector: {e}"))?;
 
-    let index = Html::parse_document(
-      &client.get("https://malapi.io")
-        .send().await?
-        .text().await?
-    );
+    let mut interval = time::interval(Duration::from_secs(1) / REQUESTS_PER_SECOND);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let limiter = Arc::new(Mutex::new(interval));
 
-    let apis = Self::scrape_apis(&index)?;
-    self.scrape_headers(&index)?;
+    let index = Self::fetch(&client, source_url, &limiter).await
+      .with_context(|| format!("could not fetch index from {source_url}"))?;
 
-    self.apis = Vec::with_capacity(apis.len());
+    // parsed document must not be held across awaits, so that updates can run
+    // in the background
+    let (apis, headers) = {
+      let index = Html::parse_document(&index);
+      (Self::scrape_apis(&index)?, Self::scrape_headers(&index)?)
+    };
 
+    // an index without categories is not worth diffing against the cache,
+    // e.g. if the source URL serves some other page
+    if apis.is_empty() || headers.is_empty() {
+      bail!("no categories found in index of {source_url}");
+    }
+    if apis.len() != headers.len() {
+      bail!("index of {source_url} has {} categories but {} headers", apis.len(), headers.len());
+    }
+
+    // category indices of every API in the index
+    let mut locations = BTreeMap::<String, Vec<usize>>::new();
     for (i, category) in apis.iter().enumerate() {
-      let bar = ProgressBar::new(category.len().try_into()?);
-      bar.set_style(
-        ProgressStyle::with_template("{prefix}: {msg}\n{spinner} {elapsed_precise} [{bar:40}] {pos}/{len}")?
-        .progress_chars("=>-")
-      );
+      for api in category {
+        locations.entry(api.clone()).or_default().push(i);
+      }
+    }
+
+    // details and categories of every API in the cache
+    let mut cached = HashMap::<String, Api>::new();
+    let mut cached_categories = BTreeMap::<String, Vec<String>>::new();
+    for (header, category) in self.headers.iter().zip(std::mem::take(&mut self.apis)) {
+      for api in category {
+        cached_categories.entry(api.name.clone()).or_default().push(header.clone());
+        cached.insert(api.name.clone(), api);
+      }
+    }
+
+    let mut diff = Diff::default();
+
+    for (name, indices) in &locations {
+      let mut current = indices.iter().map(|&i| headers[i].clone()).collect::<Vec<_>>();
+      current.sort();
+
+      match cached_categories.get_mut(name) {
+        None => diff.added.push((name.clone(), current)),
+        Some(former) => {
+          former.sort();
+          if *former != current {
+            diff.moved.push((name.clone(), former.clone(), current));
+          }
+        },
+      }
+    }
+
+    for (name, former) in cached_categories {
+      if !locations.contains_key(&name) {
+        diff.removed.push((name, former));
+      }
+    }
+
+    let outdated = locations.keys()
+      .filter(|name| cached.get(*name).is_none_or(|api| api.error.is_some()))
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let mut lengths = vec![0; apis.len()];
+    for name in &outdated {
+      for &i in &locations[name] {
+        lengths[i] += 1;
+      }
+    }
+
+    let multi = if quiet {
+      MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+      MultiProgress::new()
+    };
+    let style = ProgressStyle::with_template("{prefix}: {msg}\n{spinner} {elapsed_precise} [{bar:40}] {pos}/{len}")?
+      .progress_chars("=>-");
+
+    // categories without outdated APIs have no visible bar
+    let mut bars = Vec::with_capacity(apis.len());
+    for (header, length) in headers.iter().zip(lengths) {
+      let bar = if length > 0 {
+        multi.add(ProgressBar::new(length))
+      } else {
+        ProgressBar::hidden()
+      };
+      bar.set_style(style.clone());
       bar.enable_steady_tick(Duration::from_millis(100));
-      bar.set_prefix(self.headers[i].clone());
+      bar.set_prefix(header.clone());
+
+      bars.push(bar);
+    }
 
-      self.apis.push(HashSet::with_capacity(category.len()));
+    let semaphore = Arc::new(Semaphore::new(CONCURRENCY));
 
-      for api in category {
-        bar.set_message(api.clone());
+    let mut tasks = JoinSet::new();
 
-        let res = client.get(format!("{details_url}{api}")).send().await?;
+    for api in outdated {
+      let client = client.clone();
+      let semaphore = semaphore.clone();
+      let limiter = limiter.clone();
+      let details_selector = details_selector.clone();
+      let url = format!("{details_url}{api}");
 
-        if res.status() != reqwest::StatusCode::OK {
-          self.apis[i].insert(Api {
-            name: api.to_owned(),
+      tasks.spawn(async move {
+        let _permit = semaphore.acquire_owned().await?;
+
+        let details = Self::fetch(&client, &url, &limiter).await
+          .and_then(|page| Self::scrape_details(&api, &page, &details_selector));
+
+        anyhow::Ok(match details {
+          Ok(details) => details,
+          Err(e) => Api {
+            name: api,
+            error: Some(format!("{e:#}")),
             ..Default::default()
-          });
+          },
+        })
+      });
+    }
 
-          bar.set_message(format!("{api} unreachable!"));
-          eprintln!();
-          continue;
-        }
+    while let Some(result) = tasks.join_next().await {
+      let api = result??;
 
-        let page = res.text().await?;
-        let document = Html::parse_document(&page);
+      for &i in &locations[&api.name] {
+        let bar = &bars[i];
 
-        let content = document.select(&details_selector)
-          .collect::<Vec<_>>();
+        if api.error.is_some() {
+          bar.set_message(format!("{} failed!", api.name));
+        } else {
+          bar.set_message(api.name.clone());
+        }
 
-        let info = content.get(1)
-          .context("could not find info")?
-          .text().collect::<String>().trim().to_string();
+        bar.inc(1);
+        if Some(bar.position()) == bar.length() {
+          bar.set_message("done!");
+          bar.finish();
+        }
+      }
 
-        let library = content.get(2)
-          .context("could not find library")?
-          .text().collect::<String>().trim().to_string();
+      cached.insert(api.name.clone(), api);
+    }
 
-        let documentation = content.get(4)
-          .context("could not find documentation")?
-          .text().collect::<String>().trim().to_string();
+    self.apis = apis.iter()
+      .map(|category| category.iter().map(|name| cached[name].clone()).collect())
+      .collect();
+    self.headers = headers;
+    self.scraped = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+    self.snapshot = None;
 
-        self.apis[i].insert(Api {
-          name: api.to_owned(),
-          info,
-          library,
-          documentation
-        });
+    if quiet {
+      return Ok(diff);
+    }
 
-        bar.inc(1);
+    if diff.is_empty() {
+      eprintln!("No APIs were added, removed or moved.");
+    } else {
+      eprint!("{diff}");
+    }
+
+    let failures = self.failures();
+    if !failures.is_empty() {
+      eprintln!("{} API(s) are missing details, run with --update to retry:", failures.len());
+      for (category, api) in failures {
+        eprintln!("  {category}: {}: {}", api.name, api.error.as_deref().unwrap_or_default());
       }
-      bar.set_message("done!");
-      bar.finish();
     }
 
-    Ok(())
+    Ok(diff)
   }
-  
-  /// Load cache from `apis.mpk` file if it exists, update and create if not.
-  /// If no valid home directory is found, it still updates the `Cache` struct
-  /// but does not save for future execution
-  pub async fn load(update: bool) -> Result<Cache> {
-    let mut cache = Cache::default();
 
-    if let Some(cache_dir) = dirs::cache_dir() {
-      let cache_file = cache_dir.join(format!("{}/data.mpk", env!("CARGO_PKG_NAME")));
+  /// Updates `cache` with `options`, or fails if built without the
+  /// `scrape` feature. `cache_file` is only used for the error message.
+  #[allow(unused_variables, unused_mut)]
+  async fn refreshed(mut cache: Cache, cache_file: Option<&Path>, options: &LoadOptions) -> Result<Cache> {
+    #[cfg(feature = "scrape")]
+    {
+      cache.update(&options.source_url, options.quiet).await?;
 
-      if cache_file.exists() && update {
-        fs::remove_file(&cache_file)?;
-      }
+      Ok(cache)
+    }
 
-      if cache_file.exists() {
-        let input_stream = fs::File::open(&cache_file)?;
+    #[cfg(not(feature = "scrape"))]
+    match cache_file {
+      Some(cache_file) => bail!(
+        "no cache found at {}, and pescan was built without the `scrape` feature \
+        or an embedded snapshot to create one. Copy data.mpk there from a build with networking.",
+        cache_file.display()
+      ),
+      None => bail!(
+        "no cache directory found, and pescan was built without the `scrape` feature \
+        or an embedded snapshot to create a cache"
+      ),
+    }
+  }
 
-        cache = rmp_serde::from_read(&input_stream).context("corrupted cache, run with --update to fix")?;
-      } else {
-        let cache_dir = cache_file.parent().context("invalid cache directory path")?;
-        let mut output_stream: File;
+  /// Loads embedded snapshot, if one was available at build time
+  fn snapshot() -> Result<Option<Cache>> {
+    if SNAPSHOT.is_empty() {
+      return Ok(None);
+    }
+
+    let mut cache = schema::decode(SNAPSHOT).context("corrupted embedded snapshot")?;
+    cache.snapshot = SNAPSHOT_DATE.map(String::from);
+
+    Ok(Some(cache))
+  }
+
+  /// Load cache from `apis.mpk` file if it exists, falling back to the embedded
+  /// snapshot, or create it if neither exists. With [LoadOptions::update], the
+  /// cache (or snapshot) is updated incrementally. Caches written by
+  /// older versions are migrated to [SCHEMA_VERSION]. If no valid home
+  /// directory is found, it still updates the `Cache` struct but does not save
+  /// for future execution. Without the `scrape` feature, the cache or snapshot
+  /// must already exist. APIs of [LoadOptions::sources] and then
+  /// [LoadOptions::overlay] are merged on top but never saved.
+  pub async fn load(options: &LoadOptions) -> Result<Cache> {
+    let mut cache = Self::load_stored(options).await?;
+
+    // every stored API was scraped
+    for category in &mut cache.apis {
+      *category = std::mem::take(category).into_iter()
+        .map(|api| Api { sources: vec![String::from(MALAPI)], ..api })
+        .collect();
+    }
+
+    let source_url = cache.file_header.as_ref()
+      .map_or(DEFAULT_SOURCE_URL, |header| header.source_url.as_str());
+    let attribution = format!("Data provided by mrd0x & contributors via {source_url}.");
+    cache.attributions.push((String::from(MALAPI), attribution));
+
+    for spec in &options.sources {
+      cache.merge_source(spec.open().as_ref())
+        .with_context(|| format!("invalid {} source {}", spec.kind, spec.path.display()))?;
+    }
+
+    if let Some(overlay) = &options.overlay {
+      cache.merge_overlay(overlay)
+        .with_context(|| format!("invalid overlay {}", overlay.display()))?;
+    }
+
+    Ok(cache)
+  }
+
+  /// Loads cache from disk or the embedded snapshot, updating it if
+  /// requested or missing
+  async fn load_stored(options: &LoadOptions) -> Result<Cache> {
+    let update = options.update;
+
+    if update && cfg!(not(feature = "scrape")) {
+      bail!("--update requires pescan to be built with the `scrape` feature");
+    }
 
-        cache.update().await?;
+    let Some(cache_file) = Self::path() else {
+      eprintln!("Could not find a valid home directory for user! Data will not be cached!");
 
-        fs::create_dir_all(cache_dir)?;
-        output_stream = fs::File::create_new(&cache_file)?;
-        rmp_serde::encode::write(&mut output_stream, &cache)?;
+      return match Self::snapshot()? {
+        Some(snapshot) if !update => Ok(snapshot),
+        snapshot => Self::refreshed(snapshot.unwrap_or_default(), None, options).await,
+      };
+    };
+
+    let cached: Option<Cache> = if cache_file.exists() {
+      match schema::decode(&fs::read(&cache_file)?) {
+        Ok(cache) => Some(cache),
+        // rebuilt from scratch below
+        Err(_) if update => None,
+        Err(e) => return Err(e).context("corrupted cache, run with --update to fix"),
       }
     } else {
-      eprintln!("Could not find a valid home directory for user! Data will not be cached!");
-      cache.update().await?;
-    }
+      None
+    };
+
+    let mut cache = match cached {
+      Some(cache) if !update => cache,
+      Some(cache) => Self::refreshed(cache, Some(&cache_file), options).await?,
+      None => match Self::snapshot()? {
+        Some(snapshot) if !update => return Ok(snapshot),
+        snapshot => Self::refreshed(snapshot.unwrap_or_default(), Some(&cache_file), options).await?,
+      },
+    };
+
+    let header = match &cache.file_header {
+      Some(header) if !update && header.version < SCHEMA_VERSION => FileHeader {
+        version: SCHEMA_VERSION,
+        // unversioned caches did not record when they were created
+        created: match header.created {
+          0 => fs::metadata(&cache_file)?.modified()?.duration_since(UNIX_EPOCH)?.as_secs(),
+          created => created,
+        },
+        source_url: header.source_url.clone(),
+      },
+      Some(_) if !update => return Ok(cache),
+      _ => FileHeader::new(&options.source_url),
+    };
+
+    cache.write(&cache_file, header)?;
 
     Ok(cache)
   }
 
+  /// Path of the cache file, if the user has a cache directory
+  pub fn path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|cache_dir| cache_dir.join(format!("{}/data.mpk", env!("CARGO_PKG_NAME"))))
+  }
+
+  /// Writes cache to `cache_file` with `header`
+  fn write(&mut self, cache_file: &Path, header: FileHeader) -> Result<()> {
+    let cache_dir = cache_file.parent().context("invalid cache directory path")?;
+    fs::create_dir_all(cache_dir)?;
+
+    // written next to the cache first, so a failed write keeps the old cache
+    let partial_file = cache_file.with_extension("mpk.partial");
+    fs::write(&partial_file, schema::encode(self, &header)?)?;
+    fs::rename(&partial_file, cache_file)?;
+
+    self.file_header = Some(header);
+
+    Ok(())
+  }
+
+  /// Replaces the cache file with this cache, returning its path
+  pub fn save(&mut self) -> Result<PathBuf> {
+    let cache_file = Self::path().context("could not find a valid home directory for user")?;
+    let source_url = self.file_header.as_ref()
+      .map_or(DEFAULT_SOURCE_URL, |header| &header.source_url);
+
+    self.write(&cache_file, FileHeader::new(source_url))?;
+
+    Ok(cache_file)
+  }
+
+  /// Time the cache was last updated, or created if it was never updated,
+  /// in seconds since the Unix epoch, or `None` if unknown
+  pub fn last_updated(&self) -> Option<u64> {
+    self.scraped
+      .or(self.file_header.as_ref().map(|header| header.created).filter(|&created| created > 0))
+  }
+
+  /// Time since [Cache::last_updated], or `None` if unknown
+  pub fn age(&self) -> Option<Duration> {
+    let last_updated = UNIX_EPOCH + Duration::from_secs(self.last_updated()?);
+
+    Some(SystemTime::now().duration_since(last_updated).unwrap_or_default())
+  }
+
+  /// Get APIs whose details could not be scraped, with their category,
+  /// sorted by category and name
+  pub fn failures(&self) -> Vec<(&String, &Api)> {
+    let mut failures = Vec::new();
+
+    for (header, category) in self.headers.iter().zip(self.apis.iter()) {
+      let mut failed = category.iter()
+        .filter(|api| api.error.is_some())
+        .collect::<Vec<_>>();
+      failed.sort_by(|a, b| a.name.cmp(&b.name));
+
+      failures.extend(failed.into_iter().map(|api| (header, api)));
+    }
+
+    failures
+  }
+
   /// Get [Api] based on category and name for detail lookup
   pub fn get_api(&self, category_index: usize, name: &str) -> Option<&Api> {
     let lookup = Api {
@@ -207,6 +713,14 @@ impl Cache {
     self.apis[category_index].get(&lookup)
   }
 
+  /// Credits of the sources which at least one API of the cache came from
+  pub fn attributions(&self) -> Vec<&String> {
+    self.attributions.iter()
+      .filter(|(name, _)| self.apis.iter().flatten().any(|api| api.sources.contains(name)))
+      .map(|(_, attribution)| attribution)
+      .collect()
+  }
+
   /// Get API list from cache
   pub fn get_apis(&self) -> Vec<HashSet<String>> {
     self.apis.iter().map(|category| {
@@ -0,0 +1,69 @@
+//! Merges overlays of local API definitions, exported in the same
+//! format as [Portable](super::Portable), on top of a [Cache].
+
+use anyhow::{Result, Context};
+
+use std::fs;
+use std::path::Path;
+
+use super::{Cache, PortableFormat, LOCAL};
+
+impl Cache {
+  /// Merges APIs of overlay at `path` on top of cache, flagged as local.
+  /// Overlay APIs replace cached APIs of the same name in the same category,
+  /// keeping cached details the overlay leaves empty, and categories missing
+  /// from the cache are appended
+  pub fn merge_overlay(&mut self, path: &Path) -> Result<()> {
+    let format = PortableFormat::from_path(path).unwrap_or(PortableFormat::JSON);
+    let overlay = Self::import(&fs::read_to_string(path).context("could not read overlay")?, format)?;
+
+    for (header, apis) in overlay.headers.into_iter().zip(overlay.apis) {
+      for mut api in apis {
+        api.sources = vec![String::from(LOCAL)];
+        self.merge(header.clone(), api, true);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cache::{Api, MALAPI};
+
+  #[test]
+  fn merge_overlay_marks_local() {
+    let mut cache = Cache::import(
+      r#"{ "categories": [{ "name": "Injection", "apis": [{ "name": "VirtualAllocEx", "info": "cached", "library": "kernel32.dll" }] }] }"#,
+      PortableFormat::JSON
+    ).unwrap();
+    cache.apis[0] = std::mem::take(&mut cache.apis[0]).into_iter()
+      .map(|api| Api { sources: vec![String::from(MALAPI)], ..api })
+      .collect();
+
+    let path = std::env::temp_dir().join(format!("pescan-{}-overlay.yaml", std::process::id()));
+    fs::write(&path, "\
+categories:
+  - name: injection
+    apis:
+      - name: VirtualAllocEx
+        info: overlaid
+  - name: Local
+    apis:
+      - name: NtMapViewOfSection
+").unwrap();
+
+    let result = cache.merge_overlay(&path);
+    fs::remove_file(&path).unwrap();
+    result.unwrap();
+
+    assert_eq!(cache.headers, ["Injection", "Local"]);
+
+    let overlaid = cache.get_api(0, "VirtualAllocEx").unwrap();
+    assert_eq!((overlaid.info.as_str(), overlaid.library.as_str()), ("overlaid", "kernel32.dll"));
+    assert_eq!(overlaid.sources, [MALAPI, LOCAL]);
+    assert_eq!(cache.get_api(1, "NtMapViewOfSection").unwrap().sources, [LOCAL]);
+  }
+}
@@ -0,0 +1,144 @@
+//! Provides [Portable] struct, a human-readable form of [Cache] for
+//! exporting to and importing from JSON or YAML.
+
+use serde::{Serialize, Deserialize};
+use serde_with::skip_serializing_none;
+use anyhow::{Result, Context, bail};
+use clap::ValueEnum;
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{Api, Cache, FileHeader, DEFAULT_SOURCE_URL};
+
+/// Formats of exported caches
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PortableFormat {
+  /// JavaScript Object Notation
+  JSON,
+  /// Yet Another Markup Language
+  YAML,
+}
+
+impl PortableFormat {
+  /// Format of `path` from its extension, if it is `json`, `yaml` or `yml`
+  pub fn from_path(path: &Path) -> Option<PortableFormat> {
+    match path.extension().map(|extension| extension.to_ascii_lowercase()).as_ref().and_then(|extension| extension.to_str()) {
+      Some("json") => Some(PortableFormat::JSON),
+      Some("yaml" | "yml") => Some(PortableFormat::YAML),
+      _ => None,
+    }
+  }
+}
+
+/// Technique category of [Portable] with its APIs, sorted by name
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortableCategory {
+  /// Name of technique category
+  pub name: String,
+  /// APIs in category
+  pub apis: Vec<Api>,
+}
+
+/// Human-readable form of [Cache]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Portable {
+  /// Source from which API data was scraped
+  pub source_url: Option<String>,
+  /// Time API data was last updated, in seconds since the Unix epoch
+  pub scraped: Option<u64>,
+  /// Technique categories in order of [Cache::headers]
+  pub categories: Vec<PortableCategory>,
+}
+
+impl Cache {
+  /// Converts cache to its human-readable form
+  pub fn to_portable(&self) -> Portable {
+    let categories = self.headers.iter().zip(self.apis.iter())
+      .map(|(header, category)| {
+        let mut apis = category.iter().cloned().collect::<Vec<_>>();
+        apis.sort_by(|a, b| a.name.cmp(&b.name));
+
+        PortableCategory { name: header.clone(), apis }
+      })
+      .collect();
+
+    Portable {
+      source_url: self.file_header.as_ref().map(|header| header.source_url.clone()),
+      scraped: self.last_updated(),
+      categories,
+    }
+  }
+
+  /// Creates cache from its human-readable form, rejecting nameless or
+  /// duplicate APIs and categories
+  pub fn from_portable(portable: Portable) -> Result<Cache> {
+    let mut cache = Cache {
+      scraped: portable.scraped,
+      file_header: Some(FileHeader::new(portable.source_url.as_deref().unwrap_or(DEFAULT_SOURCE_URL))),
+      ..Default::default()
+    };
+
+    for category in portable.categories {
+      if cache.headers.contains(&category.name) {
+        bail!("duplicate category `{}`", category.name);
+      }
+
+      let mut apis = HashSet::with_capacity(category.apis.len());
+
+      for api in category.apis {
+        if api.name.is_empty() {
+          bail!("API without name in category `{}`", category.name);
+        }
+        if apis.contains(&api) {
+          bail!("duplicate API `{}` in category `{}`", api.name, category.name);
+        }
+
+        apis.insert(api);
+      }
+
+      cache.headers.push(category.name);
+      cache.apis.push(apis);
+    }
+
+    Ok(cache)
+  }
+
+  /// Exports cache as `format`
+  pub fn export(&self, format: PortableFormat) -> Result<String> {
+    let portable = self.to_portable();
+
+    Ok(match format {
+      PortableFormat::JSON => serde_json::to_string_pretty(&portable)? + "\n",
+      PortableFormat::YAML => serde_yml::to_string(&portable)?,
+    })
+  }
+
+  /// Imports cache exported as `format`
+  pub fn import(exported: &str, format: PortableFormat) -> Result<Cache> {
+    let portable: Portable = match format {
+      PortableFormat::JSON => serde_json::from_str(exported).context("invalid JSON cache")?,
+      PortableFormat::YAML => serde_yml::from_str(exported).context("invalid YAML cache")?,
+    };
+
+    Self::from_portable(portable)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn import_rejects_unknown_fields() {
+    let api = |key: &str| format!(r#"{{ "categories": [{{ "name": "Injection", "apis": [{{ "name": "A", "{key}": "" }}] }}] }}"#);
+
+    assert!(Cache::import(&api("info"), PortableFormat::JSON).is_ok());
+    assert!(Cache::import(&api("infos"), PortableFormat::JSON).is_err());
+    assert!(Cache::import("categories: []\nscrapped: 0\n", PortableFormat::YAML).is_err());
+    assert!(Cache::import("categories:\n  - name: Injection\n    api: []\n", PortableFormat::YAML).is_err());
+  }
+}
@@ -0,0 +1,160 @@
+//! Provides [FileHeader] struct and the encoding of cache files, with
+//! migrations of caches written by older versions of pescan.
+//!
+//! Versioned cache files start with [MAGIC], followed by a [FileHeader] and
+//! the [Cache] itself, both encoded as MessagePack maps so that fields can be
+//! added with `#[serde(default)]` without a new schema version.
+
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, Context, bail};
+
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Api, Cache};
+
+/// Version of the cache file format written by this version of pescan.
+/// Version 0 is the unversioned format without a header.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Bytes at the start of versioned cache files
+const MAGIC: &[u8] = b"PESCANDB";
+
+/// Header of a cache file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FileHeader {
+  /// Version of the cache file format
+  pub version: u32,
+  /// Time at which the cache was created, in seconds since the Unix epoch
+  pub created: u64,
+  /// Source from which API data was scraped
+  pub source_url: String,
+}
+
+impl FileHeader {
+  /// Creates header of the current version for a cache created now
+  pub fn new(source_url: &str) -> FileHeader {
+    FileHeader {
+      version: SCHEMA_VERSION,
+      created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+      source_url: source_url.to_owned(),
+    }
+  }
+}
+
+/// [Api] as written by version 0
+#[derive(Deserialize)]
+struct ApiV0 {
+  name: String,
+  info: String,
+  library: String,
+  documentation: String,
+  #[serde(default)]
+  error: Option<String>,
+}
+
+/// [Cache] as written by version 0, as an array of fields rather than a map
+#[derive(Deserialize)]
+struct CacheV0 {
+  headers: Vec<String>,
+  apis: Vec<Vec<ApiV0>>,
+}
+
+impl From<CacheV0> for Cache {
+  fn from(cache: CacheV0) -> Cache {
+    Cache {
+      headers: cache.headers,
+      apis: cache.apis.into_iter()
+        .map(|category| category.into_iter()
+          .map(|api| Api {
+            name: api.name,
+            info: api.info,
+            library: api.library,
+            documentation: api.documentation,
+            error: api.error,
+            techniques: Vec::new(),
+            sources: Vec::new(),
+          })
+          .collect::<HashSet<_>>())
+        .collect(),
+      ..Default::default()
+    }
+  }
+}
+
+/// Encodes `cache` as the contents of a cache file with `header`
+pub fn encode(cache: &Cache, header: &FileHeader) -> Result<Vec<u8>> {
+  let mut bytes = MAGIC.to_vec();
+  let mut serializer = rmp_serde::Serializer::new(&mut bytes).with_struct_map();
+
+  header.serialize(&mut serializer)?;
+  cache.serialize(&mut serializer)?;
+
+  Ok(bytes)
+}
+
+/// Decodes the contents of a cache file of any version, setting
+/// [Cache::file_header] to its header. Caches of version 0 are given a
+/// header with the default source and an unknown creation time of 0.
+pub fn decode(bytes: &[u8]) -> Result<Cache> {
+  let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+    let cache: CacheV0 = rmp_serde::from_slice(bytes).context("invalid unversioned cache")?;
+    let mut cache = Cache::from(cache);
+
+    cache.file_header = Some(FileHeader {
+      version: 0,
+      created: 0,
+      source_url: String::from(super::DEFAULT_SOURCE_URL),
+    });
+
+    return Ok(cache);
+  };
+
+  let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+  let header = FileHeader::deserialize(&mut deserializer).context("invalid cache header")?;
+
+  let mut cache = match header.version {
+    1 => Cache::deserialize(&mut deserializer).context("invalid cache")?,
+    version => bail!(
+      "cache schema version {version} is newer than supported version {SCHEMA_VERSION}, \
+      upgrade pescan or run with --update to replace it"
+    ),
+  };
+
+  cache.file_header = Some(header);
+
+  Ok(cache)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decode_v0() {
+    // version 0 wrote structs as arrays, with `error` added later
+    let bytes = rmp_serde::to_vec(&(
+      ["Injection", "Helper"],
+      [
+        vec![("VirtualAllocEx", "allocates memory", "kernel32.dll", "https://example.com", Some("timeout"))],
+        vec![],
+      ],
+    )).unwrap();
+
+    let cache = decode(&bytes).unwrap();
+    let header = cache.file_header.as_ref().unwrap();
+    assert_eq!((header.version, header.created), (0, 0));
+    assert_eq!(cache.headers, ["Injection", "Helper"]);
+    assert!(cache.apis[1].is_empty());
+
+    let api = cache.apis[0].iter().next().unwrap();
+    assert_eq!(api.name, "VirtualAllocEx");
+    assert_eq!(api.library, "kernel32.dll");
+    assert_eq!(api.error.as_deref(), Some("timeout"));
+
+    // migrated caches are written as the current version
+    let cache = decode(&encode(&cache, &FileHeader::new(&header.source_url)).unwrap()).unwrap();
+    assert_eq!(cache.file_header.unwrap().version, SCHEMA_VERSION);
+    assert_eq!(cache.apis[0].iter().next().unwrap().info, "allocates memory");
+  }
+}
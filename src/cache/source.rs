@@ -0,0 +1,396 @@
+//! Provides [Source] trait for knowledge sources of API definitions other
+//! than the scraped data, and its implementations for local datasets.
+
+use serde::Deserialize;
+use anyhow::{Result, Context, bail};
+use walkdir::WalkDir;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{Api, Cache};
+
+/// Name of the source of scraped APIs
+pub const MALAPI: &str = "malapi";
+/// Name of the source of APIs defined in a local overlay
+pub const LOCAL: &str = "local";
+
+/// Knowledge source of API definitions, merged on top of the scraped data
+/// by [Cache::load]
+pub trait Source {
+  /// Name which APIs of source are attributed to
+  fn name(&self) -> String;
+  /// Credit shown in output if any API of source is loaded
+  fn attribution(&self) -> String;
+  /// APIs of source with their technique categories
+  fn apis(&self) -> Result<Vec<(String, Api)>>;
+}
+
+/// Kinds of [Source] which can be configured
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+  /// [TechniqueMap] file
+  Attack,
+  /// Directory of [CapaRules]
+  Capa,
+}
+
+impl fmt::Display for SourceKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      SourceKind::Attack => "attack",
+      SourceKind::Capa => "capa",
+    })
+  }
+}
+
+/// [Source] of a kind at a path, parsed from `KIND=PATH`
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceSpec {
+  /// Kind of source
+  pub kind: SourceKind,
+  /// File or directory of source
+  pub path: PathBuf,
+}
+
+impl FromStr for SourceSpec {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (kind, path) = s.split_once('=')
+      .ok_or_else(|| format!("expected KIND=PATH, found `{s}`"))?;
+
+    let kind = match kind.trim() {
+      "attack" => SourceKind::Attack,
+      "capa" => SourceKind::Capa,
+      kind => return Err(format!("unknown source kind `{kind}`, expected attack or capa")),
+    };
+
+    Ok(SourceSpec { kind, path: PathBuf::from(path.trim()) })
+  }
+}
+
+impl SourceSpec {
+  /// Opens source, which is only read by [Source::apis]
+  pub fn open(&self) -> Box<dyn Source> {
+    match self.kind {
+      SourceKind::Attack => Box::new(TechniqueMap { path: self.path.clone() }),
+      SourceKind::Capa => Box::new(CapaRules { path: self.path.clone() }),
+    }
+  }
+}
+
+/// Row of [TechniqueMap]
+#[derive(Deserialize)]
+struct Mapping {
+  name: String,
+  #[serde(default)]
+  technique: Option<String>,
+  #[serde(default)]
+  category: Option<String>,
+  #[serde(default)]
+  info: Option<String>,
+  #[serde(default)]
+  library: Option<String>,
+  #[serde(default)]
+  documentation: Option<String>,
+}
+
+/// CSV or JSON list mapping APIs to ATT&CK techniques, with `name` and
+/// `category` columns (or keys) and optionally `technique`, `info`,
+/// `library` and `documentation`. Techniques are reported with hits of
+/// their APIs
+pub struct TechniqueMap {
+  path: PathBuf,
+}
+
+impl Source for TechniqueMap {
+  fn name(&self) -> String {
+    format!("{}:{}", SourceKind::Attack, file_name(&self.path))
+  }
+
+  fn attribution(&self) -> String {
+    format!("Technique mappings from {}.", self.path.display())
+  }
+
+  fn apis(&self) -> Result<Vec<(String, Api)>> {
+    let path = &self.path;
+    let mappings: Vec<Mapping> = match path.extension().and_then(|extension| extension.to_str()) {
+      Some("csv") => csv::Reader::from_path(path)
+        .and_then(|mut reader| reader.deserialize().collect())
+        .with_context(|| format!("invalid technique mapping {}", path.display()))?,
+      Some("json") => serde_json::from_str(&fs::read_to_string(path)
+          .with_context(|| format!("could not read technique mapping {}", path.display()))?)
+        .with_context(|| format!("invalid technique mapping {}", path.display()))?,
+      _ => bail!("unsupported technique mapping {}, expected .csv or .json", path.display()),
+    };
+
+    mappings.into_iter()
+      .map(|mapping| {
+        let Some(category) = mapping.category.filter(|c| !c.is_empty()) else {
+          bail!("API `{}` in {} has no category", mapping.name, path.display());
+        };
+
+        Ok((category, Api {
+          name: mapping.name,
+          info: mapping.info.unwrap_or_default(),
+          library: mapping.library.unwrap_or_default(),
+          documentation: mapping.documentation.unwrap_or_default(),
+          techniques: mapping.technique.into_iter().filter(|t| !t.is_empty()).collect(),
+          ..Default::default()
+        }))
+      })
+      .collect()
+  }
+}
+
+/// Directory of capa rules in YAML, whose `api` features are listed under
+/// the top level of the rule's namespace, with the rule name as info
+pub struct CapaRules {
+  path: PathBuf,
+}
+
+/// Collects names of `api` features in capa rule `value`
+fn capa_features<'a>(value: &'a serde_json::Value, features: &mut Vec<&'a str>) {
+  match value {
+    serde_json::Value::Object(map) => for (key, value) in map {
+      match (key.as_str(), value) {
+        ("api", serde_json::Value::String(api)) => features.push(api),
+        _ => capa_features(value, features),
+      }
+    },
+    serde_json::Value::Array(values) => for value in values {
+      capa_features(value, features);
+    },
+    _ => (),
+  }
+}
+
+impl Source for CapaRules {
+  fn name(&self) -> String {
+    format!("{}:{}", SourceKind::Capa, file_name(&self.path))
+  }
+
+  fn attribution(&self) -> String {
+    format!("API lists from capa rules in {}.", self.path.display())
+  }
+
+  fn apis(&self) -> Result<Vec<(String, Api)>> {
+    let mut apis = Vec::new();
+
+    for entry in WalkDir::new(&self.path).sort_by_file_name() {
+      let entry = entry.with_context(|| format!("could not read capa rules {}", self.path.display()))?;
+      let path = entry.path();
+
+      if !entry.file_type().is_file()
+        || !matches!(path.extension().and_then(|extension| extension.to_str()), Some("yml" | "yaml")) {
+        continue;
+      }
+
+      let rule = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(serde_yml::from_str::<serde_json::Value>(&contents)?))
+        .with_context(|| format!("invalid capa rule {}", path.display()))?;
+
+      let meta = &rule["rule"]["meta"];
+      let (Some(name), Some(namespace)) = (meta["name"].as_str(), meta["namespace"].as_str()) else {
+        continue;
+      };
+      let category = namespace.split('/').next().unwrap_or(namespace);
+
+      let mut features = Vec::new();
+      capa_features(&rule["rule"]["features"], &mut features);
+
+      for feature in features {
+        // regular expressions and .NET members are not PE imports
+        if feature.starts_with('/') || feature.contains("::") {
+          continue;
+        }
+
+        let (library, api) = match feature.rsplit_once('.') {
+          Some((library, api)) => (format!("{}.dll", library.to_ascii_lowercase()), api),
+          None => (String::new(), feature),
+        };
+
+        apis.push((category.to_owned(), Api {
+          name: api.to_owned(),
+          info: name.to_owned(),
+          library,
+          ..Default::default()
+        }));
+      }
+    }
+
+    Ok(apis)
+  }
+}
+
+/// File name of `path` for naming sources
+fn file_name(path: &Path) -> String {
+  path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+impl Cache {
+  /// Merges `api` into category `header` (compared case-insensitively),
+  /// which is appended if missing. Details of `api` replace cached details
+  /// if `overwrite`, otherwise they only fill empty ones. The techniques and
+  /// sources of both are kept.
+  pub(super) fn merge(&mut self, header: String, mut api: Api, overwrite: bool) {
+    let index = match self.headers.iter().position(|h| h.eq_ignore_ascii_case(&header)) {
+      Some(index) => index,
+      None => {
+        self.headers.push(header);
+        self.apis.push(Default::default());
+        self.apis.len() - 1
+      },
+    };
+
+    if let Some(cached) = self.apis[index].get(&api) {
+      for (field, cached) in [
+        (&mut api.info, &cached.info),
+        (&mut api.library, &cached.library),
+        (&mut api.documentation, &cached.documentation),
+      ] {
+        if field.is_empty() || !overwrite && !cached.is_empty() {
+          field.clone_from(cached);
+        }
+      }
+
+      for technique in cached.techniques.iter().rev() {
+        if !api.techniques.contains(technique) {
+          api.techniques.insert(0, technique.clone());
+        }
+      }
+      for source in cached.sources.iter().rev() {
+        if !api.sources.contains(source) {
+          api.sources.insert(0, source.clone());
+        }
+      }
+    }
+
+    self.apis[index].replace(api);
+  }
+
+  /// Merges APIs of `source` on top of cache without replacing details of
+  /// cached APIs
+  pub fn merge_source(&mut self, source: &dyn Source) -> Result<()> {
+    let name = source.name();
+
+    for (header, mut api) in source.apis()? {
+      if api.name.is_empty() {
+        bail!("API without name in category `{header}` of source {name}");
+      }
+
+      api.sources = vec![name.clone()];
+      self.merge(header, api, false);
+    }
+
+    self.attributions.push((name, source.attribution()));
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cache::PortableFormat;
+
+  /// Source of fixed APIs named `test`
+  struct Fixed(Vec<(&'static str, &'static str, &'static str)>);
+
+  impl Source for Fixed {
+    fn name(&self) -> String {
+      String::from("test")
+    }
+
+    fn attribution(&self) -> String {
+      String::from("Test APIs.")
+    }
+
+    fn apis(&self) -> Result<Vec<(String, Api)>> {
+      Ok(self.0.iter()
+        .map(|(category, name, info)| (category.to_string(), Api {
+          name: name.to_string(),
+          info: info.to_string(),
+          ..Default::default()
+        }))
+        .collect())
+    }
+  }
+
+  /// Cache with `VirtualAllocEx` in category `Injection`, scraped with info
+  /// but without library
+  fn cache() -> Cache {
+    let mut cache = Cache::import(
+      r#"{ "categories": [{ "name": "Injection", "apis": [{ "name": "VirtualAllocEx", "info": "cached" }] }] }"#,
+      PortableFormat::JSON
+    ).unwrap();
+
+    cache.apis[0] = std::mem::take(&mut cache.apis[0]).into_iter()
+      .map(|api| Api { sources: vec![String::from(MALAPI)], ..api })
+      .collect();
+    cache
+  }
+
+  #[test]
+  fn merge_fills_or_overwrites() {
+    let api = || Api {
+      name: String::from("VirtualAllocEx"),
+      info: String::from("merged"),
+      library: String::from("kernel32.dll"),
+      ..Default::default()
+    };
+
+    for (overwrite, info) in [(false, "cached"), (true, "merged")] {
+      let mut cache = cache();
+      cache.merge(String::from("INJECTION"), api(), overwrite);
+
+      assert_eq!(cache.headers, ["Injection"], "overwrite: {overwrite}");
+      let merged = cache.get_api(0, "VirtualAllocEx").unwrap();
+      assert_eq!((merged.info.as_str(), merged.library.as_str()), (info, "kernel32.dll"), "overwrite: {overwrite}");
+    }
+  }
+
+  #[test]
+  fn merge_source_appends_categories_and_sources() {
+    let mut cache = cache();
+    cache.merge_source(&Fixed(vec![
+      ("injection", "VirtualAllocEx", "sourced"),
+      ("Evasion", "IsDebuggerPresent", "checks for a debugger"),
+    ])).unwrap();
+
+    assert_eq!(cache.headers, ["Injection", "Evasion"]);
+
+    let merged = cache.get_api(0, "VirtualAllocEx").unwrap();
+    assert_eq!(merged.info, "cached");
+    assert_eq!(merged.sources, [MALAPI, "test"]);
+
+    let appended = cache.get_api(1, "IsDebuggerPresent").unwrap();
+    assert_eq!(appended.sources, ["test"]);
+    assert_eq!(cache.attributions(), [&String::from("Test APIs.")]);
+  }
+
+  #[test]
+  fn technique_map_requires_category() {
+    let apis = |contents: &str| {
+      let path = std::env::temp_dir().join(format!("pescan-{}-{}.csv", std::process::id(), contents.len()));
+      fs::write(&path, contents).unwrap();
+
+      let apis = TechniqueMap { path: path.clone() }.apis();
+      fs::remove_file(&path).unwrap();
+      apis
+    };
+
+    let mapped = apis("name,technique,category\nCreateProcessW,T1106,Execution\n").unwrap();
+    assert_eq!(mapped.len(), 1);
+    assert_eq!(mapped[0].0, "Execution");
+    assert_eq!(mapped[0].1.techniques, ["T1106"]);
+
+    assert!(apis("name,technique,category\nVirtualAlloc,T1055,\n").is_err());
+  }
+}
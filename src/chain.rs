@@ -0,0 +1,60 @@
+//! Provides [ChainStep] struct and [build] function for ordering matched
+//! technique categories into a potential attack chain.
+
+use serde::Serialize;
+
+use crate::imports::ImportKind;
+use crate::output::SuspectImport;
+
+/// Technique categories in kill-chain order, paired with the phase they
+/// represent in an attack
+pub const PHASES: [(&str, &str); 8] = [
+  ("Enumeration", "surveys the host, its processes and files"),
+  ("Injection", "injects code into other processes"),
+  ("Evasion", "hides its artifacts and evades defenses"),
+  ("Spying", "collects keystrokes, screens and user activity"),
+  ("Internet", "communicates with remote hosts"),
+  ("Anti-Debugging", "detects or hinders debuggers and analysis"),
+  ("Ransomware", "encrypts or destroys data"),
+  ("Helper", "resolves and loads further functionality"),
+];
+
+/// Phase used for categories which are not part of [PHASES]
+const UNKNOWN_PHASE: &str = "uses other suspicious functionality";
+
+/// Single step of a potential attack chain
+#[derive(Serialize)]
+pub struct ChainStep<'a> {
+  /// Technique category of step
+  pub category: String,
+  /// Description of kill-chain phase
+  pub phase: &'static str,
+  /// APIs justifying step
+  pub apis: Vec<&'a String>,
+}
+
+/// Orders categories with at least one suspect import by [PHASES], leaving
+/// out APIs which are only named by strings. Categories not found in
+/// [PHASES] are appended in their original order.
+pub fn build<'a>(headers: &[String], suspect_imports: &[Vec<SuspectImport<'a>>])
+  -> Vec<ChainStep<'a>> {
+  let position = |header: &String| PHASES.iter()
+    .position(|(category, _)| category.eq_ignore_ascii_case(header));
+
+  let imported = |i: usize| suspect_imports[i].iter()
+    .filter(|import| import.import != ImportKind::Dynamic);
+
+  let mut matched: Vec<usize> = (0..headers.len().min(suspect_imports.len()))
+    .filter(|&i| imported(i).next().is_some())
+    .collect();
+
+  matched.sort_by_key(|&i| position(&headers[i]).unwrap_or(PHASES.len()));
+
+  matched.into_iter().map(|i| {
+    ChainStep {
+      category: headers[i].clone(),
+      phase: position(&headers[i]).map_or(UNKNOWN_PHASE, |p| PHASES[p].1),
+      apis: imported(i).map(|import| import.name).collect()
+    }
+  }).collect()
+}
@@ -0,0 +1,52 @@
+//! Provides [Config] struct for loading persistent settings from
+//! `pescan/config.toml` in the user's config directory.
+
+use serde::Deserialize;
+use anyhow::{Result, Context};
+
+use crate::cache::{StalePolicy, SourceSpec};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Settings which apply to every run, overridden by command line options
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Source of API data for cache updates
+  pub source_url: Option<String>,
+  /// Maximum age of the cache in days
+  pub max_age: Option<u64>,
+  /// Action taken when the cache is older than `max_age`
+  pub stale: Option<StalePolicy>,
+  /// Additional sources of API definitions
+  pub sources: Vec<SourceSpec>,
+  /// Overlay of local API definitions merged on top of the cache
+  pub overlay: Option<PathBuf>,
+}
+
+/// Path of `pescan/overlay.yaml` in the user's config directory, if it exists
+pub fn default_overlay() -> Option<PathBuf> {
+  dirs::config_dir()
+    .map(|config_dir| config_dir.join(format!("{}/overlay.yaml", env!("CARGO_PKG_NAME"))))
+    .filter(|path| path.exists())
+}
+
+impl Config {
+  /// Load config from `pescan/config.toml` in the user's config directory,
+  /// or defaults if it does not exist
+  pub fn load() -> Result<Config> {
+    let Some(config_dir) = dirs::config_dir() else {
+      return Ok(Config::default());
+    };
+    let path = config_dir.join(format!("{}/config.toml", env!("CARGO_PKG_NAME")));
+
+    match fs::read_to_string(&path) {
+      Ok(config) => toml::from_str(&config)
+        .with_context(|| format!("invalid config {}", path.display())),
+      Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+      Err(e) => Err(e).with_context(|| format!("could not read config {}", path.display())),
+    }
+  }
+}
@@ -0,0 +1,133 @@
+//! Provides exit codes of the process, [Failure] tags for selecting the exit
+//! code of errors and [FailOn] enum for gating on scan findings.
+
+use anyhow::{Error, Result};
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::imports::ImportKind;
+use crate::output::Output;
+
+/// Exit code when findings exceed a `--fail-on` threshold
+pub const FINDINGS: u8 = 3;
+/// Exit code when the sample could not be read or parsed
+pub const PARSE: u8 = 4;
+/// Exit code when the cache could not be loaded or updated
+pub const CACHE: u8 = 5;
+/// Exit code of any other error
+pub const OTHER: u8 = 1;
+
+/// Tag attached to errors with [WithFailure] to select their exit code
+#[derive(Clone, Copy, Debug)]
+pub enum Failure {
+  /// Sample could not be read or parsed
+  Parse,
+  /// Cache could not be loaded or updated
+  Cache,
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Failure::Parse => "parse failure",
+      Failure::Cache => "cache failure",
+    })
+  }
+}
+
+impl Failure {
+  /// Tags `error` with failure, keeping its message and causes
+  pub fn tag(self, error: impl Into<Error>) -> Error {
+    Error::new(Tagged { failure: self, error: error.into() })
+  }
+}
+
+/// Error tagged with a [Failure], displayed as the error itself
+#[derive(Debug)]
+struct Tagged {
+  failure: Failure,
+  error: Error,
+}
+
+impl fmt::Display for Tagged {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(&self.error, f)
+  }
+}
+
+impl std::error::Error for Tagged {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    self.error.source()
+  }
+}
+
+/// Tags errors of results with a [Failure]
+pub trait WithFailure<T> {
+  /// Tags error, if any, with `failure`
+  fn failure(self, failure: Failure) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> WithFailure<T> for Result<T, E> {
+  fn failure(self, failure: Failure) -> Result<T> {
+    self.map_err(|error| failure.tag(error))
+  }
+}
+
+/// Exit code for `error`, based on the outermost [Failure] it is tagged with
+pub fn code(error: &Error) -> u8 {
+  match error.chain().find_map(|error| error.downcast_ref::<Tagged>()).map(|tagged| tagged.failure) {
+    Some(Failure::Parse) => PARSE,
+    Some(Failure::Cache) => CACHE,
+    None => OTHER,
+  }
+}
+
+/// Condition for exiting with [FINDINGS], parsed from either a technique
+/// category or a minimum score
+#[derive(Clone)]
+pub enum FailOn {
+  /// Any API imported in technique category (case insensitive), not only
+  /// named by a string
+  Category(String),
+  /// Score is at least value
+  Score(f64),
+}
+
+impl FromStr for FailOn {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s.trim().is_empty() {
+      return Err(String::from("expected category or score"));
+    }
+
+    Ok(match s.trim().parse::<f64>() {
+      Ok(score) if !score.is_finite() => return Err(format!("invalid score `{score}`, expected a finite number")),
+      Ok(score) => FailOn::Score(score),
+      Err(_) => FailOn::Category(s.trim().to_owned()),
+    })
+  }
+}
+
+impl FailOn {
+  /// Whether condition refers to a category which is not in `headers`
+  pub fn is_unknown(&self, headers: &[String]) -> bool {
+    match self {
+      FailOn::Category(category) => !headers.iter()
+        .any(|header| header.eq_ignore_ascii_case(category)),
+      FailOn::Score(_) => false,
+    }
+  }
+
+  /// Whether findings of any sample in `output` meet the condition
+  pub fn triggered(&self, output: &Output) -> bool {
+    output.samples.iter().any(|sample| match self {
+      FailOn::Category(category) => output.headers.iter()
+        .zip(sample.suspect_imports.iter())
+        .any(|(header, imports)| header.eq_ignore_ascii_case(category)
+          && imports.iter().any(|import| import.import != ImportKind::Dynamic)),
+      FailOn::Score(score) => sample.score.value >= *score,
+    })
+  }
+}
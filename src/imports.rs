@@ -0,0 +1,497 @@
+//! Provides [Import] struct for APIs imported by a sample, [collect] for
+//! gathering them from both the regular and delay-load import tables,
+//! resolution of imports by ordinal to API names, and [strings] for finding
+//! names of APIs resolved at runtime.
+
+use goblin::pe::{PE, options::ParseOptions, utils};
+use serde::Serialize;
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Size of a delay-load descriptor in bytes
+const DESCRIPTOR_SIZE: usize = 32;
+
+/// Maximum number of delay-load imports read from a sample, so that crafted
+/// tables cannot exhaust memory
+const MAX_DELAY_LOAD_IMPORTS: usize = 1 << 16;
+
+/// Minimum length of strings extracted by [strings]
+const MIN_STRING_LEN: usize = 4;
+
+/// Names of APIs exported by ordinal from well-known system DLLs, whose
+/// ordinals are stable across Windows versions
+const ORDINALS: &[(&str, &[(u16, &str)])] = &[
+  ("ws2_32.dll", WS2_32),
+  ("wsock32.dll", WSOCK32),
+  ("oleaut32.dll", &[
+    (2, "SysAllocString"), (3, "SysReAllocString"), (4, "SysAllocStringLen"),
+    (5, "SysReAllocStringLen"), (6, "SysFreeString"), (7, "SysStringLen"),
+    (8, "VariantInit"), (9, "VariantClear"), (10, "VariantCopy"),
+    (11, "VariantCopyInd"), (12, "VariantChangeType"), (15, "SafeArrayCreate"),
+    (16, "SafeArrayDestroy"), (17, "SafeArrayGetDim"), (18, "SafeArrayGetElemsize"),
+    (19, "SafeArrayGetUBound"), (20, "SafeArrayGetLBound"), (21, "SafeArrayLock"),
+    (22, "SafeArrayUnlock"), (23, "SafeArrayAccessData"), (24, "SafeArrayUnaccessData"),
+    (25, "SafeArrayGetElement"), (26, "SafeArrayPutElement"), (27, "SafeArrayCopy"),
+    (147, "VariantChangeTypeEx"), (149, "SysStringByteLen"),
+    (150, "SysAllocStringByteLen"), (161, "LoadTypeLib"), (162, "LoadRegTypeLib"),
+    (183, "LoadTypeLibEx"),
+  ]),
+];
+
+/// Ordinals of `ws2_32.dll`
+const WS2_32: &[(u16, &str)] = &[
+  (1, "accept"), (2, "bind"), (3, "closesocket"), (4, "connect"),
+  (5, "getpeername"), (6, "getsockname"), (7, "getsockopt"), (8, "htonl"),
+  (9, "htons"), (10, "ioctlsocket"), (11, "inet_addr"), (12, "inet_ntoa"),
+  (13, "listen"), (14, "ntohl"), (15, "ntohs"), (16, "recv"),
+  (17, "recvfrom"), (18, "select"), (19, "send"), (20, "sendto"),
+  (21, "setsockopt"), (22, "shutdown"), (23, "socket"), (51, "gethostbyaddr"),
+  (52, "gethostbyname"), (53, "getprotobyname"), (54, "getprotobynumber"),
+  (55, "getservbyname"), (56, "getservbyport"), (57, "gethostname"),
+  (111, "WSAGetLastError"), (112, "WSASetLastError"), (115, "WSAStartup"),
+  (116, "WSACleanup"), (151, "__WSAFDIsSet"),
+];
+
+/// Ordinals of `wsock32.dll`, which follow Winsock 1.1 rather than
+/// `ws2_32.dll` for `inet_addr`, `inet_ntoa` and `ioctlsocket`
+const WSOCK32: &[(u16, &str)] = &[
+  (1, "accept"), (2, "bind"), (3, "closesocket"), (4, "connect"),
+  (5, "getpeername"), (6, "getsockname"), (7, "getsockopt"), (8, "htonl"),
+  (9, "htons"), (10, "inet_addr"), (11, "inet_ntoa"), (12, "ioctlsocket"),
+  (13, "listen"), (14, "ntohl"), (15, "ntohs"), (16, "recv"),
+  (17, "recvfrom"), (18, "select"), (19, "send"), (20, "sendto"),
+  (21, "setsockopt"), (22, "shutdown"), (23, "socket"), (51, "gethostbyaddr"),
+  (52, "gethostbyname"), (53, "getprotobyname"), (54, "getprotobynumber"),
+  (55, "getservbyname"), (56, "getservbyport"), (57, "gethostname"),
+  (111, "WSAGetLastError"), (112, "WSASetLastError"), (115, "WSAStartup"),
+  (116, "WSACleanup"), (151, "__WSAFDIsSet"),
+];
+
+/// How a sample imports an API, in order from most to least ordinary
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportKind {
+  /// Regular import table, resolved when the sample is loaded
+  #[default]
+  Static,
+  /// Delay-load import table, resolved on the first call
+  DelayLoad,
+  /// Not imported, but named by a string of the sample, presumably to be
+  /// resolved at runtime with `GetProcAddress`
+  #[serde(rename = "dynamic-string")]
+  Dynamic,
+}
+
+impl fmt::Display for ImportKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      ImportKind::Static => "static",
+      ImportKind::DelayLoad => "delay-load",
+      ImportKind::Dynamic => "dynamically resolved (string)",
+    })
+  }
+}
+
+/// API imported by a sample
+pub struct Import {
+  /// Name of API, or `ORDINAL n` like goblin's
+  /// [Import](goblin::pe::import::Import)s if imported by an unknown ordinal
+  pub name: String,
+  /// DLL which API is imported from
+  pub dll: String,
+  /// Import table API is listed in
+  pub kind: ImportKind,
+  /// Ordinal API is imported by, if not imported by name
+  pub ordinal: Option<u16>,
+}
+
+/// Name of API exported by `dll` as `ordinal`, if it is well-known
+pub fn resolve_ordinal(dll: &str, ordinal: u16) -> Option<&'static str> {
+  ORDINALS.iter()
+    .find(|(name, _)| name.eq_ignore_ascii_case(dll))
+    .and_then(|(_, ordinals)| ordinals.iter().find(|(o, _)| *o == ordinal))
+    .map(|(_, name)| *name)
+}
+
+/// Whether `dll` which a sample imports an API from is `library` which
+/// documents it. Unknown DLLs and libraries, API set contracts, the split of
+/// `kernel32.dll` into `kernelbase.dll` and `wsock32.dll` forwarding to
+/// `ws2_32.dll` are not considered unexpected
+pub fn expected_library(dll: &str, library: &str) -> bool {
+  let stem = |name: &str| {
+    let name = name.trim().to_ascii_lowercase();
+    name.strip_suffix(".dll").map(str::to_owned).unwrap_or(name)
+  };
+  let alias = |a: &str, b: &str| [("kernel32", "kernelbase"), ("wsock32", "ws2_32")].iter()
+    .any(|pair| [a, b].iter().all(|name| *name == pair.0 || *name == pair.1));
+
+  let dll = stem(dll);
+
+  library.trim().is_empty()
+    || dll.is_empty()
+    || dll.starts_with("api-ms-win-")
+    || dll.starts_with("ext-ms-win-")
+    || library.split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+      .filter(|library| !library.is_empty())
+      .map(stem)
+      .any(|library| library == dll || alias(&library, &dll))
+}
+
+/// Canonical form of API `name` for matching variants of the same API:
+/// without an ANSI or Unicode (`A`/`W`) suffix, without an `Ex` suffix,
+/// and with the `Nt` prefix for `Zw` system calls
+pub fn canonical(name: &str) -> String {
+  normalize(name, true)
+}
+
+/// [Canonical](canonical) form of API `name` which keeps an `Ex` suffix, for
+/// telling `Ex` variants apart from other variants of the same API
+pub fn variant(name: &str) -> String {
+  normalize(name, false)
+}
+
+fn normalize(name: &str, strip_ex: bool) -> String {
+  let mut canonical = name;
+
+  // only strip suffixes which follow the end of a word, like `ProcessW`
+  let word_end = |name: &str, suffix: usize| name.len() > suffix
+    && matches!(name.as_bytes()[name.len() - suffix - 1], b'a'..=b'z' | b'0'..=b'9');
+
+  if (canonical.ends_with('A') || canonical.ends_with('W')) && word_end(canonical, 1) {
+    canonical = &canonical[..canonical.len() - 1];
+  }
+  if strip_ex && canonical.ends_with("Ex") && word_end(canonical, 2) {
+    canonical = &canonical[..canonical.len() - 2];
+  }
+
+  match canonical.strip_prefix("Zw") {
+    Some(rest) if rest.starts_with(|c: char| c.is_ascii_uppercase()) => format!("Nt{rest}"),
+    _ => canonical.to_owned(),
+  }
+}
+
+/// Collects imports of `pe` parsed from `bytes` from both import tables,
+/// naming imports by ordinal with [resolve_ordinal] where possible
+pub fn collect(pe: &PE, bytes: &[u8]) -> Vec<Import> {
+  let mut imports = pe.imports.iter()
+    .map(|import| Import {
+      name: import.name.to_string(),
+      dll: import.dll.to_owned(),
+      kind: ImportKind::Static,
+      // goblin stores the hint of imports by name as their ordinal
+      ordinal: import.name.strip_prefix("ORDINAL ").map(|_| import.ordinal),
+    })
+    .chain(delay_load(pe, bytes))
+    .collect::<Vec<_>>();
+
+  for import in &mut imports {
+    if let Some(name) = import.ordinal.and_then(|ordinal| resolve_ordinal(&import.dll, ordinal)) {
+      import.name = name.to_owned();
+    }
+  }
+
+  imports
+}
+
+/// Extracts ASCII and UTF-16LE strings of printable characters, of at least
+/// [MIN_STRING_LEN] characters, which could be names of APIs
+pub fn strings(bytes: &[u8]) -> HashSet<String> {
+  let printable = |b: u8| (0x20..0x7f).contains(&b).then_some(b);
+  let mut strings = HashSet::new();
+
+  let mut collect = |units: &mut dyn Iterator<Item = Option<u8>>| {
+    let mut run = Vec::new();
+
+    for unit in units.chain([None]) {
+      match unit {
+        Some(b) => run.push(b),
+        None => {
+          if run.len() >= MIN_STRING_LEN && run.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_') {
+            strings.insert(String::from_utf8_lossy(&run).into_owned());
+          }
+          run.clear();
+        },
+      }
+    }
+  };
+
+  collect(&mut bytes.iter().map(|&b| printable(b)));
+  for start in 0..2 {
+    collect(&mut bytes.get(start..).unwrap_or_default().chunks_exact(2)
+      .map(|unit| if unit[1] == 0 { printable(unit[0]) } else { None }));
+  }
+
+  strings
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  bytes.get(offset..offset.checked_add(4)?)
+    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+  Some(u64::from(read_u32(bytes, offset)?) | u64::from(read_u32(bytes, offset.checked_add(4)?)?) << 32)
+}
+
+fn read_str(bytes: &[u8], offset: usize) -> Option<&str> {
+  let bytes = bytes.get(offset..)?;
+  let end = bytes.iter().position(|&b| b == 0)?;
+
+  std::str::from_utf8(&bytes[..end]).ok()
+}
+
+/// Reads the delay-load imports of `pe` parsed from `bytes`, stopping at
+/// the first malformed descriptor or thunk. Only descriptors within the
+/// directory are read, name tables shared by several descriptors are read
+/// once, and no more than [MAX_DELAY_LOAD_IMPORTS] imports are read.
+pub fn delay_load(pe: &PE, bytes: &[u8]) -> Vec<Import> {
+  let mut imports = Vec::new();
+
+  let Some(optional_header) = pe.header.optional_header else {
+    return imports;
+  };
+  let Some(directory) = optional_header.data_directories.get_delay_import_descriptor() else {
+    return imports;
+  };
+
+  let options = ParseOptions::default();
+  let file_alignment = optional_header.windows_fields.file_alignment;
+  let offset = |rva: u32| utils::find_offset(rva as usize, &pe.sections, file_alignment, &options);
+
+  let Some(descriptors) = offset(directory.virtual_address) else {
+    return imports;
+  };
+  let mut name_tables = HashSet::new();
+
+  for index in 0..directory.size as usize / DESCRIPTOR_SIZE {
+    let descriptor = descriptors + index * DESCRIPTOR_SIZE;
+    let field = |index: usize| read_u32(bytes, descriptor + index * 4);
+    let (Some(attributes), Some(dll_name), Some(name_table)) = (field(0), field(1), field(4)) else {
+      break;
+    };
+    if dll_name == 0 {
+      break;
+    }
+
+    // descriptors of old linkers hold virtual addresses instead of RVAs
+    let rva = |address: u32| if attributes & 1 == 0 {
+      address.checked_sub(pe.image_base as u32)
+    } else {
+      Some(address)
+    };
+
+    let Some(dll) = rva(dll_name).and_then(offset).and_then(|o| read_str(bytes, o)) else {
+      break;
+    };
+    let Some(mut thunk) = rva(name_table).and_then(offset) else {
+      break;
+    };
+
+    if !name_tables.insert(thunk) {
+      continue;
+    }
+
+    loop {
+      if imports.len() >= MAX_DELAY_LOAD_IMPORTS {
+        return imports;
+      }
+
+      let (entry, by_ordinal) = if pe.is_64 {
+        match read_u64(bytes, thunk) {
+          Some(entry) => (entry, entry & 1 << 63 != 0),
+          None => break,
+        }
+      } else {
+        match read_u32(bytes, thunk) {
+          Some(entry) => (u64::from(entry), entry & 1 << 31 != 0),
+          None => break,
+        }
+      };
+      if entry == 0 {
+        break;
+      }
+
+      let (name, ordinal) = if by_ordinal {
+        let ordinal = (entry & 0xffff) as u16;
+        (format!("ORDINAL {ordinal}"), Some(ordinal))
+      } else {
+        // skip the hint before the name
+        match rva(entry as u32).and_then(offset).and_then(|o| read_str(bytes, o + 2)) {
+          Some(name) => (name.to_owned(), None),
+          None => break,
+        }
+      };
+
+      imports.push(Import { name, dll: dll.to_owned(), kind: ImportKind::DelayLoad, ordinal });
+      thunk += if pe.is_64 { 8 } else { 4 };
+    }
+  }
+
+  imports
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn canonical_names() {
+    assert_eq!(canonical("ProcessW"), "Process");
+    assert_eq!(canonical("SetWindowsHookExA"), "SetWindowsHook");
+    assert_eq!(canonical("ZwX"), "NtX");
+    assert_eq!(canonical("Zwrite"), "Zwrite");
+    assert_eq!(canonical("WSA"), "WSA");
+    assert_eq!(canonical("Ex"), "Ex");
+
+    assert_eq!(variant("SetWindowsHookExA"), "SetWindowsHookEx");
+    assert_eq!(variant("ZwCreateThreadEx"), "NtCreateThreadEx");
+    assert_eq!(variant("GetModuleHandleW"), "GetModuleHandle");
+  }
+
+  #[test]
+  fn ordinals() {
+    for (dll, ordinal, name) in [
+      ("ws2_32.dll", 10, Some("ioctlsocket")), ("ws2_32.dll", 11, Some("inet_addr")),
+      ("ws2_32.dll", 12, Some("inet_ntoa")), ("WS2_32.DLL", 115, Some("WSAStartup")),
+      ("wsock32.dll", 10, Some("inet_addr")), ("wsock32.dll", 11, Some("inet_ntoa")),
+      ("wsock32.dll", 12, Some("ioctlsocket")), ("wsock32.dll", 23, Some("socket")),
+      ("oleaut32.dll", 2, Some("SysAllocString")), ("oleaut32.dll", 1, None),
+      ("kernel32.dll", 1, None),
+    ] {
+      assert_eq!(resolve_ordinal(dll, ordinal), name, "{dll} ordinal {ordinal}");
+    }
+  }
+
+  #[test]
+  fn expected_libraries() {
+    for (dll, library, expected) in [
+      ("KERNEL32.dll", "kernel32.dll", true),
+      ("kernel32", "Kernel32.DLL", true),
+      ("kernel32.dll", " kernel32 ", true),
+      ("api-ms-win-core-processthreads-l1-1-0.dll", "kernel32.dll", true),
+      ("ext-ms-win-ntuser-window-l1-1-0.dll", "user32.dll", true),
+      ("kernelbase.dll", "kernel32.dll", true),
+      ("kernel32.dll", "KernelBase.dll", true),
+      ("wsock32.dll", "ws2_32.dll", true),
+      ("WS2_32.dll", "wsock32.dll", true),
+      ("kernelbase.dll", "kernel32.dll / kernelbase.dll", true),
+      ("advapi32.dll", "kernel32.dll, advapi32.dll", true),
+      ("ntdll.dll", "", true),
+      ("", "kernel32.dll", true),
+      ("ntdll.dll", "kernel32.dll", false),
+      ("user32.dll", "kernel32.dll / kernelbase.dll", false),
+      ("ws2_32.dll", "kernel32.dll", false),
+    ] {
+      assert_eq!(expected_library(dll, library), expected, "{dll} documented in {library}");
+    }
+  }
+
+  fn put(bytes: &mut Vec<u8>, value: u64, size: usize) {
+    bytes.extend(&value.to_le_bytes()[..size]);
+  }
+
+  /// Builds a minimal PE file which delay-loads `Sleep` and ordinal 3 from
+  /// `kernel32.dll` by `descriptors` identical descriptors of RVAs or, if
+  /// `va`, virtual addresses, in a directory of `directory_size` bytes
+  fn delay_load_pe(is_64: bool, va: bool, descriptors: usize, directory_size: u64) -> Vec<u8> {
+    let image_base: u64 = if is_64 { 0x1_4000_0000 } else { 0x40_0000 };
+    let width = if is_64 { 8 } else { 4 };
+    let address = |rva: u64| if va { image_base + rva } else { rva };
+
+    // section at RVA 0x1000: DLL name, hint and name, name table, address
+    // table and descriptors followed by a null descriptor
+    let mut section = b"kernel32.dll\0\0\0\0\0\0Sleep\0".to_vec();
+    for table in [0x20, 0x40] {
+      section.resize(table, 0);
+      for entry in [address(0x1010), 1 << (width * 8 - 1) | 3, 0] {
+        put(&mut section, entry, width);
+      }
+    }
+    section.resize(0x60, 0);
+    for _ in 0..descriptors {
+      for field in [u64::from(!va), address(0x1000), 0, address(0x1040), address(0x1020), 0, 0, 0] {
+        put(&mut section, field, 4);
+      }
+    }
+    section.resize(0x200, 0);
+
+    let mut pe = b"MZ".to_vec();
+    pe.resize(0x3c, 0);
+    put(&mut pe, 0x80, 4);
+    pe.resize(0x80, 0);
+    pe.extend(b"PE\0\0");
+
+    // file header
+    for (value, size) in [
+      (if is_64 { 0x8664 } else { 0x14c }, 2), (1, 2), (0, 4), (0, 4), (0, 4),
+      (if is_64 { 0xf0 } else { 0xe0 }, 2), (if is_64 { 0x22 } else { 0x102 }, 2),
+    ] {
+      put(&mut pe, value, size);
+    }
+
+    // optional header
+    for (value, size) in [(if is_64 { 0x20b } else { 0x10b }, 2), (0, 2), (0x200, 4), (0, 4), (0, 4), (0x1000, 4), (0x1000, 4)] {
+      put(&mut pe, value, size);
+    }
+    if !is_64 {
+      put(&mut pe, 0x1000, 4);
+    }
+    put(&mut pe, image_base, width);
+    for (value, size) in [
+      (0x1000, 4), (0x200, 4), (6, 2), (0, 2), (0, 2), (0, 2), (6, 2), (0, 2),
+      (0, 4), (0x2000, 4), (0x200, 4), (0, 4), (3, 2), (0x8140, 2),
+      (0x10_0000, width), (0x1000, width), (0x10_0000, width), (0x1000, width), (0, 4), (16, 4),
+    ] {
+      put(&mut pe, value, size);
+    }
+    for directory in 0..16 {
+      let (rva, size) = if directory == 13 { (0x1060, directory_size) } else { (0, 0) };
+      put(&mut pe, rva, 4);
+      put(&mut pe, size, 4);
+    }
+
+    // section header
+    pe.extend(b".rdata\0\0");
+    for (value, size) in [(0x200, 4), (0x1000, 4), (0x200, 4), (0x200, 4), (0, 4), (0, 4), (0, 2), (0, 2), (0x4000_0040, 4)] {
+      put(&mut pe, value, size);
+    }
+
+    pe.resize(0x200, 0);
+    pe.extend(section);
+    pe
+  }
+
+  #[test]
+  fn delay_load_descriptors() {
+    for (is_64, va) in [(false, false), (false, true), (true, false), (true, true)] {
+      let bytes = delay_load_pe(is_64, va, 1, 0x40);
+      let pe = PE::parse(&bytes).unwrap();
+
+      let imports = delay_load(&pe, &bytes).into_iter()
+        .map(|import| (import.name, import.dll, import.kind == ImportKind::DelayLoad, import.ordinal))
+        .collect::<Vec<_>>();
+
+      assert_eq!(imports, [
+        (String::from("Sleep"), String::from("kernel32.dll"), true, None),
+        (String::from("ORDINAL 3"), String::from("kernel32.dll"), true, Some(3)),
+      ], "64-bit: {is_64}, virtual addresses: {va}");
+    }
+  }
+
+  #[test]
+  fn delay_load_bounds() {
+    let names = |descriptors, directory_size| {
+      let bytes = delay_load_pe(false, false, descriptors, directory_size);
+      let pe = PE::parse(&bytes).unwrap();
+
+      delay_load(&pe, &bytes).into_iter().map(|import| import.name).collect::<Vec<_>>()
+    };
+
+    // descriptors sharing a name table are only read once
+    assert_eq!(names(12, 13 * 0x20), ["Sleep", "ORDINAL 3"]);
+    // descriptors beyond the directory are not read
+    assert!(names(1, 0x1f).is_empty());
+  }
+}
@@ -0,0 +1,132 @@
+//! Provides [Input] struct and [collect] function for gathering sample
+//! files from paths and directories given on the command line.
+
+use anyhow::{Result, Context, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::scan::Skipped;
+
+/// Selection of files collected from directories
+#[derive(Clone, Default)]
+pub struct CollectOptions {
+  /// Expand directories recursively
+  pub recursive: bool,
+  /// Globs which files must match, if any
+  pub include: Vec<String>,
+  /// Globs which files must not match
+  pub exclude: Vec<String>,
+}
+
+/// Sample file to be scanned
+pub struct Input {
+  /// Path of sample
+  pub path: Utf8PathBuf,
+  /// Whether sample was found in a directory rather than given explicitly.
+  /// Discovered files which are not PE files are skipped.
+  pub discovered: bool,
+}
+
+/// Samples collected by [collect], along with entries of directories which
+/// were skipped
+#[derive(Default)]
+pub struct Collected {
+  /// Samples to be scanned, in order
+  pub inputs: Vec<Input>,
+  /// Directory entries which could not be read or whose paths are not valid
+  /// UTF-8, in the order they were found
+  pub skipped: Vec<Skipped>,
+}
+
+/// Builds [GlobSet] from `patterns`
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+  let mut builder = GlobSetBuilder::new();
+
+  for pattern in patterns {
+    builder.add(Glob::new(pattern).with_context(|| format!("invalid glob `{pattern}`"))?);
+  }
+
+  Ok(builder.build()?)
+}
+
+/// Collects samples from `samples`. Directories are expanded to their files
+/// (recursively with [CollectOptions::recursive]), filtered by the include and
+/// exclude globs of `options` and sorted by path. Explicitly given files are
+/// never filtered. Files given or found more than once are only collected the
+/// first time. Entries of directories which cannot be read are skipped.
+pub fn collect(samples: &[Utf8PathBuf], options: &CollectOptions) -> Result<Collected> {
+  let include = glob_set(&options.include)?;
+  let exclude = glob_set(&options.exclude)?;
+
+  // globs may match either the whole path or just the file name
+  let is_match = |set: &GlobSet, path: &Utf8Path| {
+    set.is_match(path) || path.file_name().is_some_and(|name| set.is_match(name))
+  };
+  let matches = |path: &Utf8Path| {
+    (options.include.is_empty() || is_match(&include, path)) && !is_match(&exclude, path)
+  };
+
+  let mut inputs: Vec<Input> = Vec::new();
+  let mut skipped: Vec<Skipped> = Vec::new();
+  let mut seen: HashMap<PathBuf, usize> = HashMap::new();
+
+  // files are compared by canonical path, so `dir` and `dir/a.exe` overlap
+  let mut push = |path: Utf8PathBuf, discovered: bool| {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone().into());
+
+    match seen.get(&canonical) {
+      Some(&index) => inputs[index].discovered &= discovered,
+      None => {
+        seen.insert(canonical, inputs.len());
+        inputs.push(Input { path, discovered });
+      },
+    }
+  };
+
+  for path in samples {
+    if path.is_dir() {
+      let walker = WalkDir::new(path)
+        .max_depth(if options.recursive { usize::MAX } else { 1 })
+        .sort_by_file_name();
+
+      for entry in walker {
+        let entry = match entry {
+          Ok(entry) => entry,
+          Err(e) => {
+            let path = e.path().map_or_else(|| path.to_string(), |path| path.display().to_string());
+            let reason = e.io_error().map_or_else(|| e.to_string(), ToString::to_string);
+            skipped.push(Skipped { path, reason });
+            continue;
+          },
+        };
+
+        if !entry.file_type().is_file() {
+          continue;
+        }
+
+        let file = match Utf8PathBuf::try_from(entry.into_path()) {
+          Ok(file) => file,
+          Err(e) => {
+            let path = e.as_path().display().to_string();
+            skipped.push(Skipped { path, reason: String::from("path is not valid UTF-8") });
+            continue;
+          },
+        };
+
+        if matches(&file) {
+          push(file, true);
+        }
+      }
+    } else if path.is_file() {
+      push(path.clone(), false);
+    } else {
+      bail!("sample {path} is not a file or directory");
+    }
+  }
+
+  Ok(Collected { inputs, skipped })
+}
@@ -0,0 +1,37 @@
+#![warn(missing_docs)]
+#![allow(clippy::all)]
+
+//! PEScan is a malware analysis tool that scans portable executable (PE)
+//! files for potentially malicious Windows API imports.
+//! The library uses [HashSet](std::collections::HashSet)s for
+//! maximum efficiency in comparing import lists.
+//!
+//! A [Scanner] matches samples against a loaded [Cache](cache::Cache) and
+//! returns an owned [Report] per sample:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use pescan::{cache::{Cache, LoadOptions}, Scanner};
+//!
+//! let cache = Cache::load(&LoadOptions::default()).await?;
+//! let scanner = Scanner::new(&cache);
+//! let report = scanner.scan(&std::fs::read("sample.exe")?)?;
+//!
+//! println!("{} ({})", report.score.value, report.score.verdict);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod output;
+pub mod cache;
+pub mod chain;
+pub mod rules;
+pub mod score;
+pub mod exit;
+pub mod input;
+pub mod scan;
+pub mod archive;
+pub mod config;
+pub mod imports;
+
+pub use scan::{Scanner, Report};
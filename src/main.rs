@@ -1,164 +1,254 @@
 #![warn(missing_docs)]
 #![allow(clippy::all)]
 
-//! PEScan is a malware analysis tool that scans portable executable (PE)
-//! files for potentially malicious Windows API imports.
-//! The program uses [HashSet]s for
-//! maximum efficiency in comparing import lists.
-//! The binary can output in multiple formats and provide
-//! a potential attack chain for the sample.
+//! Command line interface of PEScan. Scans samples with a
+//! [Scanner] and outputs their reports in multiple formats, optionally
+//! with a potential attack chain for each sample.
 
-pub mod args;
-pub mod output;
-pub mod cache;
+mod args;
 
 use clap::Parser;
-use anyhow::{Result, Context, bail};
-use goblin::{Object, pe::import::Import};
+use anyhow::{Result, Context, anyhow, bail};
 
-use std::{env, fs};
-use std::collections::hash_set::HashSet;
+use indicatif::ProgressBar;
+
+use std::{env, fs, thread};
 use std::io::{Read, Write, IsTerminal};
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use pescan::{exit, input, rules, Scanner};
+use args::{Args, Command, CacheAction};
+use pescan::output::{Format, Metadata, Output, Sample};
+use pescan::cache::{self, Cache, LoadOptions, PortableFormat, StalePolicy};
+use pescan::config::{self, Config};
+use pescan::exit::{Failure, FailOn, WithFailure};
+use pescan::scan::{scan_file, scan_all, Scan, Skipped};
 
-use crate::args::Args;
-use crate::output::{Details, SuspectImport, Format, Output};
-use crate::cache::Cache;
+#[tokio::main]
+async fn main() -> ExitCode {
+  let args = Args::parse();
 
-/// Flattens `Vec` of [Import]s into `Vec` of [String]s
-fn flatten_imports(raw_imports: &[Import]) -> HashSet<String> {
-  raw_imports.iter()
-    .map(|i| i.name.to_string()).collect()
+  match run(&args).await {
+    Ok(true) => ExitCode::from(exit::FINDINGS),
+    Ok(false) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("Error: {e:?}");
+      ExitCode::from(exit::code(&e))
+    }
+  }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-  let args = Args::parse();
+/// Scans sample and writes output, returning whether any `--fail-on`
+/// condition was met
+async fn run(args: &Args) -> Result<bool> {
+  let config = Config::load()?;
+  let options = LoadOptions {
+    update: args.update,
+    source_url: args.source_url.clone()
+      .or(config.source_url)
+      .unwrap_or_else(|| String::from(cache::DEFAULT_SOURCE_URL)),
+    quiet: false,
+    sources: config.sources.iter().chain(&args.source).cloned().collect(),
+    overlay: args.overlay.clone().map(Into::into)
+      .or(config.overlay)
+      .or_else(config::default_overlay),
+  };
+
+  if let Some(Command::Cache { action }) = &args.command {
+    // exports and imports only contain the cache itself
+    manage_cache(action, &LoadOptions { sources: Vec::new(), overlay: None, ..options }).await?;
+    return Ok(false);
+  }
 
-  let cache = Cache::load(args.update).await?;
-  let apis = cache.get_apis();
+  let cache = Cache::load(&options).await.failure(Failure::Cache)?;
+
+  let max_age = args.max_age.or(config.max_age).unwrap_or(cache::DEFAULT_MAX_AGE);
+  let mut refresh = None;
+
+  if !args.update && max_age > 0
+    && cache.age().is_none_or(|age| age > Duration::from_secs(max_age.saturating_mul(24 * 60 * 60))) {
+    let age = cache.age().map_or_else(
+      || String::from("of unknown age"),
+      |age| format!("{} day(s) old", age.as_secs() / (24 * 60 * 60))
+    );
+
+    match args.stale.or(config.stale).unwrap_or_default() {
+      StalePolicy::Warn => {
+        eprintln!("Warning: cache is {age}, run with --update to refresh it.");
+      },
+      StalePolicy::Refresh => {
+        eprintln!("Cache is {age}, refreshing it while scanning, pescan will wait for the refresh before exiting.");
+
+        let options = LoadOptions { update: true, quiet: true, ..options };
+        refresh = Some(tokio::spawn(async move { Cache::load(&options).await }));
+      },
+      StalePolicy::Refuse => {
+        return Err(Failure::Cache.tag(anyhow!("cache is {age}, run with --update to refresh it")));
+      },
+    }
+  }
 
-  let mut sample_buffer: Vec<u8> = Vec::new();
+  if let Some(FailOn::Category(category)) = args.fail_on.iter()
+    .find(|fail_on| fail_on.is_unknown(&cache.headers)) {
+    bail!("unknown category `{category}` for --fail-on");
+  }
 
-  if let Some(path) = &args.sample {
-    sample_buffer = fs::read(path)?;
-  } else {
+  let mut scanner = Scanner::new(&cache).exact(args.exact).strings(!args.no_strings)
+    .weights(&args.weight);
+  for path in &args.rules {
+    scanner = scanner.rules(rules::load(path)?);
+  }
+
+  let open = || -> Result<Box<dyn Write>> {
+    Ok(if let Some(path) = &args.path {
+      Box::new(fs::File::create_new(path)?)
+    } else {
+      Box::new(std::io::stdout())
+    })
+  };
+
+  // only plain text is written before all samples are scanned, so that
+  // failed scans leave no output file behind in other formats
+  let mut buf = match &args.format {
+    Format::TXT => Some(open()?),
+    _ => None,
+  };
+
+  let sample_options = args.sample_options();
+  let mut scans: Vec<Scan> = Vec::new();
+
+  // plain text is streamed as samples are scanned, other formats
+  // are written once all samples are done
+  let mut on_scan = |scan: Scan, bar: &ProgressBar| -> Result<()> {
+    if let (Format::TXT, Some(buf)) = (&args.format, &mut buf) {
+      let sample = Sample::new(&scan, &cache.headers, &sample_options);
+
+      bar.suspend(|| -> Result<()> {
+        if !scans.is_empty() {
+          writeln!(buf).context("could not write to file")?;
+        }
+        sample.txt(&cache.headers, buf, args.width)
+      })?;
+    }
+
+    scans.push(scan);
+
+    Ok(())
+  };
+  let on_skip = |skipped: Skipped, bar: &ProgressBar| {
+    bar.suspend(|| eprintln!("Skipping {}: {}", skipped.path, skipped.reason));
+  };
+
+  if args.sample.is_empty() {
+    let mut sample_buffer: Vec<u8> = Vec::new();
     let mut stdin = std::io::stdin();
     if !stdin.is_terminal() {
-      let _ = stdin.read_to_end(&mut sample_buffer)?;
+      let _ = stdin.read_to_end(&mut sample_buffer).failure(Failure::Parse)?;
     } else {
       bail!("sample not found in [FILE] or stdin.");
     }
-  }
 
-  match Object::parse(&sample_buffer)
-    .context(if env::var("PESCAN_DOCKER") == Ok(String::from("true")) {
-      "docker container not running in interactive mode"
-    } else {
-      "could not parse sample"
-    })?
-  {
-    Object::PE(pe) => {
-      let imports = flatten_imports(&pe.imports);
+    let bar = ProgressBar::hidden();
+    let scanned = scan_file(String::from("-"), &sample_buffer, false, &scanner, &args.password)
+      .map_err(|e| if env::var("PESCAN_DOCKER") == Ok(String::from("true")) {
+        e.context("docker container not running in interactive mode")
+      } else {
+        e
+      })?;
+    for skipped in scanned.skipped {
+      on_skip(skipped, &bar);
+    }
+    for scan in scanned.scans {
+      on_scan(scan, &bar)?;
+    }
+  } else {
+    let collected = input::collect(&args.sample, &args.collect_options()).failure(Failure::Parse)?;
+    for skipped in collected.skipped {
+      on_skip(skipped, &ProgressBar::hidden());
+    }
+    let jobs = args.jobs.map_or_else(
+      || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+      NonZeroUsize::get
+    );
 
-      let mut suspicious_imports = Vec::<Vec<String>>::new();
-      let mut details: Option<Vec<Vec<Details>>> = None;
+    scan_all(&collected.inputs, jobs, &scanner, &args.password, on_scan, on_skip)?;
+  }
+
+  let samples = scans.iter()
+    .map(|scan| Sample::new(scan, &cache.headers, &sample_options))
+    .collect();
 
-      std::mem::drop(pe);
+  let output = Output { metadata: Metadata::new(&cache), headers: cache.headers, samples };
 
-      for category in apis.iter() {
-        suspicious_imports.push(category.intersection(&imports).cloned().collect());
+  match &args.format {
+    Format::CSV => {
+      if let Some(path) = &args.path {
+        output.csv_to_file(path)?;
+      } else {
+        output.csv_to_stdout()?;
       }
+    },
+    Format::TXT => (),
+    Format::JSON => output.json(&mut open()?)?,
+    Format::YAML => output.yaml(&mut open()?)?,
+    Format::TOML => output.toml(&mut open()?)?,
+    _ => unreachable!()
+  }
 
-      if [args.info, args.library, args.documentation, args.all].contains(&true) {
-        let mut matched_details = Vec::<Vec<Details>>::new();
-        for (i, category) in suspicious_imports.iter().enumerate() {
-          matched_details.push(Vec::new());
-          for import in category {
-            if let Some(api) = cache.get_api(i, import) {
-              let mut info = None;
-              let mut library = None;
-              let mut documentation = None;
-
-              if args.info || args.all {
-                info = Some(api.info.clone());
-              }
-              if args.library || args.all {
-                library = Some(api.library.clone());
-              }
-              if args.documentation || args.all {
-                documentation = Some(api.documentation.clone());
-              }
-
-              matched_details[i].push(Details { info, library, documentation });
-            } else {
-              matched_details[i].push(Details::default());
-            }
-          }
-        }
+  for source in &output.metadata.sources {
+    eprintln!("{source}");
+  }
+  if let Some(date) = &output.metadata.snapshot {
+    eprintln!("Using embedded snapshot from {date}, run with --update for the latest data.");
+  }
 
-        details = Some(matched_details);
-      }
+  if let Some(refresh) = refresh {
+    if !refresh.is_finished() {
+      eprintln!("Waiting for the cache refresh to finish.");
+    }
 
-      let mut suspect_imports: Vec<Vec<SuspectImport>> = Vec::with_capacity(suspicious_imports.len());
-
-      for i in 0..suspicious_imports.len() {
-        suspect_imports.push(Vec::new());
-        for (j, import) in suspicious_imports[i].iter().enumerate() {
-          if let Some(details) = &details {
-            suspect_imports[i].push(
-              SuspectImport {
-                name: import,
-                info: details[i][j].info.as_ref(),
-                library: details[i][j].library.as_ref(),
-                documentation: details[i][j].documentation.as_ref()
-              }
-            );
-          } else {
-            suspect_imports[i].push(
-              SuspectImport {
-                name: import,
-                info: None,
-                library: None,
-                documentation: None
-              }
-            );
-          }
-        }
-      }
+    match refresh.await? {
+      Ok(_) => eprintln!("Cache refreshed for future runs."),
+      Err(e) => eprintln!("Warning: could not refresh cache: {e:#}"),
+    }
+  }
 
-      let output = Output { headers: cache.headers, suspect_imports };
-
-      match &args.format {
-        Format::CSV => {
-          if let Some(path) = &args.path {
-            output.csv_to_file(path, &args)?;
-          } else {
-            output.csv_to_stdout(&args)?;
-          }
-        },
-        _ => {
-          let mut buf: Box<dyn Write> = if let Some(path) = &args.path {
-            Box::new(fs::File::create_new(path)?)
-          } else {
-            Box::new(std::io::stdout())
-          };
-
-          match &args.format {
-            Format::TXT => output.txt(&mut buf, &args)?,
-            Format::JSON => output.json(&mut buf)?,
-            Format::YAML => output.yaml(&mut buf)?,
-            Format::TOML => output.toml(&mut buf)?,
-            Format::CSV => unreachable!()
-          }
-        }
+  Ok(args.fail_on.iter().any(|fail_on| fail_on.triggered(&output)))
+}
+
+/// Exports or imports the cache for `pescan cache`
+async fn manage_cache(action: &CacheAction, options: &LoadOptions) -> Result<()> {
+  match action {
+    CacheAction::Export { format, path } => {
+      let format = format
+        .or(path.as_ref().and_then(|path| PortableFormat::from_path(path.as_std_path())))
+        .unwrap_or(PortableFormat::JSON);
+      let cache = Cache::load(options).await.failure(Failure::Cache)?;
+      let exported = cache.export(format)?;
+
+      if let Some(path) = path {
+        fs::File::create_new(path)?.write_all(exported.as_bytes())
+          .with_context(|| format!("could not write to {path}"))?;
+        eprintln!("Exported cache to {path}.");
+      } else {
+        print!("{exported}");
       }
     },
-    _ => {
-      bail!("invalid file type, only PE files are supported");
-    }
+    CacheAction::Import { format, path } => {
+      let format = format
+        .or(PortableFormat::from_path(path.as_std_path()))
+        .unwrap_or(PortableFormat::JSON);
+      let exported = fs::read_to_string(path)
+        .with_context(|| format!("could not read {path}"))?;
+      let mut cache = Cache::import(&exported, format)
+        .with_context(|| format!("could not import {path}"))?;
+
+      let cache_file = cache.save().failure(Failure::Cache)?;
+      eprintln!("Imported {} into {}.", path, cache_file.display());
+    },
   }
 
-  eprintln!("Data provided by mrd0x & contributors via https://malapi.io.");
-
   Ok(())
 }
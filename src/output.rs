@@ -15,7 +15,12 @@ use tabled::{
 use std::fs::File;
 use std::io::Write;
 
-use crate::args::Args;
+use crate::cache::Cache;
+use crate::chain::{self, ChainStep};
+use crate::imports::ImportKind;
+use crate::rules::Behavior;
+use crate::scan::Scan;
+use crate::score::Score;
 
 /// All possible output formats (set with -f or --format)
 #[non_exhaustive]
@@ -33,40 +38,43 @@ pub enum Format {
   CSV,
 }
 
-/// Contains optional details about imports. Set using `-i`, -`l`, and
-/// `-d` flags .
-#[derive(Default)]
-pub struct Details {
-  /// Summary of API functionality
-  pub info: Option<String>,
-  /// Library from which API is imported
-  pub library: Option<String>,
-  /// Link to API documentation
-  pub documentation: Option<String>
-}
-
 /// Contains all of the suspect API's relevant data
 #[skip_serializing_none]
 #[derive(Serialize, Tabled)]
 pub struct SuspectImport<'a> {
-  /// Name of API
+  /// Name of API in the cache
   pub name: &'a String,
+  /// Name sample imports API by, if it differs from `name`
+  #[tabled(display("display::option", ""))]
+  pub imported: Option<&'a String>,
+  /// How sample imports API
+  pub import: ImportKind,
+  /// Ordinal sample imports API by, if not imported by name
+  #[tabled(display("display::option", ""))]
+  pub ordinal: Option<u16>,
+  /// DLL sample imports API from
+  pub dll: &'a String,
   /// Summary of API functionality
   #[tabled(display("display::option", ""))]
   pub info: Option<&'a String>,
-  /// Library from which API is imported
+  /// Library which documents API
   #[tabled(display("display::option", ""))]
   pub library: Option<&'a String>,
   /// Link to API documentation
   #[tabled(display("format_url"))]
   pub documentation: Option<&'a String>,
-}
-
-impl SuspectImport<'_> {
-  fn len(&self) -> usize {
-    [self.info, self.library, self.documentation].iter()
-      .filter(|opt| opt.is_some()).count() + 1
-  }
+  /// MITRE ATT&CK technique IDs of API
+  #[tabled(display("display::option", ""))]
+  pub techniques: Option<String>,
+  /// Sources API came from
+  #[tabled(display("display::option", ""))]
+  pub sources: Option<String>,
+  /// Set if API was defined in a local overlay rather than scraped
+  #[tabled(display("format_flag", "local"))]
+  pub local: Option<bool>,
+  /// Set if `dll` is not the library which documents API
+  #[tabled(display("format_flag", "unexpected"))]
+  pub unexpected: Option<bool>,
 }
 
 /// Shortens URLs to `[link]` with OSC8 ANSI styled hyperlinks
@@ -78,31 +86,73 @@ pub fn format_url(url: &Option<&String>) -> String {
   }
 }
 
+/// Shows `label` for flags which are set
+pub fn format_flag(flag: &Option<bool>, label: &str) -> String {
+  String::from(if flag.is_some() { label } else { "" })
+}
+
+/// Joins list items with newlines for display in tables
+pub fn format_list(list: &[String]) -> String {
+  list.join("\n")
+}
+
 /// Creates a [Vec] of pairs of headers and tables constrained
-/// to a certain width (approximately).
-pub fn create_tables(output: &Output, args: &Args)
+/// to `width` (approximately), without columns no import has.
+pub fn create_tables(headers: &[String], sample: &Sample, width: usize)
   -> Vec<(String, Table)> {
-  let mut tables: Vec<(String, Table)> = Vec::with_capacity(output.headers.len());
+  let mut tables: Vec<(String, Table)> = Vec::with_capacity(headers.len());
 
-  for (i, category) in output.suspect_imports.iter().enumerate() {
-    let mut total_columns = 4;
-    let mut table = (output.headers[i].to_owned(),
+  for (i, category) in sample.suspect_imports.iter().enumerate() {
+    let mut total_columns = 12;
+    let mut table = (headers[i].to_owned(),
       Table::new(category));
 
-    if !args.info && !args.all {
+    if category.iter().all(|import| import.imported.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("imported")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.import == ImportKind::Static) {
+      table.1.with(Remove::column(ByColumnName::new("import")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.ordinal.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("ordinal")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.info.is_none()) {
       table.1.with(Remove::column(ByColumnName::new("info")));
       total_columns -= 1;
     }
-    if !args.library && !args.all {
+    if category.iter().all(|import| import.library.is_none()) {
       table.1.with(Remove::column(ByColumnName::new("library")));
       total_columns -= 1;
     }
-    if !args.documentation && !args.all {
+    if category.iter().all(|import| import.library.is_none() && import.unexpected.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("dll")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.documentation.is_none()) {
       table.1.with(Remove::column(ByColumnName::new("documentation")));
       total_columns -= 1;
     }
+    if category.iter().all(|import| import.techniques.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("techniques")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.sources.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("sources")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.local.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("local")));
+      total_columns -= 1;
+    }
+    if category.iter().all(|import| import.unexpected.is_none()) {
+      table.1.with(Remove::column(ByColumnName::new("unexpected")));
+      total_columns -= 1;
+    }
 
-    table.1.modify(Rows::new(0..), Width::wrap(args.width / total_columns).keep_words(true));
+    table.1.modify(Rows::new(0..), Width::wrap(width / total_columns).keep_words(true));
 
     tables.push(table);
   }
@@ -110,28 +160,173 @@ pub fn create_tables(output: &Output, args: &Args)
   tables
 }
 
-/// Wrapper to group headers and suspect imports for outputting
-pub struct Output<'b> {
-  /// [Vec] of technique categories
-  pub headers: Vec<String>,
+/// Details of suspect imports selected for a [Sample]
+#[derive(Clone, Copy, Default)]
+pub struct SampleOptions {
+  /// Include summary of API functionality
+  pub info: bool,
+  /// Include library which documents API
+  pub library: bool,
+  /// Include link to API documentation
+  pub documentation: bool,
+  /// Include sources API came from
+  pub sources: bool,
+  /// Build potential attack chain
+  pub chain: bool,
+}
+
+/// Suspect imports and findings of a single sample
+pub struct Sample<'b> {
+  /// Path of sample
+  pub path: &'b String,
+  /// SHA-256 hash of sample
+  pub sha256: &'b String,
   /// 2D [Vec] of suspect APIs by technique category
-  pub suspect_imports: Vec<Vec<SuspectImport<'b>>>
+  pub suspect_imports: Vec<Vec<SuspectImport<'b>>>,
+  /// Suspicion score and verdict
+  pub score: &'b Score,
+  /// Behaviours detected from combinations of APIs
+  pub behaviors: &'b [Behavior],
+  /// Potential attack chain, set with [SampleOptions::chain]
+  pub chain: Option<Vec<ChainStep<'b>>>
 }
 
-impl Serialize for Output<'_> {
+impl<'b> Sample<'b> {
+  /// Selects details of suspect imports in `scan` set in `options` and
+  /// builds attack chain
+  pub fn new(scan: &'b Scan, headers: &[String], options: &SampleOptions) -> Sample<'b> {
+    let suspect_imports: Vec<Vec<SuspectImport>> = scan.report.categories.iter()
+      .map(|category| category.hits.iter()
+        .map(|hit| SuspectImport {
+          name: &hit.name,
+          imported: (hit.imported != hit.name).then_some(&hit.imported),
+          import: hit.import,
+          ordinal: hit.ordinal,
+          dll: &hit.dll,
+          info: options.info.then_some(&hit.info),
+          library: options.library.then_some(&hit.library),
+          // APIs of sources and overlays mostly have no documentation to link
+          documentation: options.documentation.then_some(&hit.documentation)
+            .filter(|documentation| !documentation.is_empty()),
+          techniques: (!hit.techniques.is_empty()).then(|| hit.techniques.join(", ")),
+          sources: options.sources.then(|| hit.sources.join(", ")),
+          local: hit.local.then_some(true),
+          unexpected: hit.unexpected.then_some(true),
+        })
+        .collect())
+      .collect();
+
+    let chain = options.chain.then(|| chain::build(headers, &suspect_imports));
+
+    Sample {
+      path: &scan.path,
+      sha256: &scan.report.sha256,
+      suspect_imports,
+      score: &scan.report.score,
+      behaviors: &scan.report.behaviors,
+      chain
+    }
+  }
+
+  /// Output to `buf` as plain text, with tables at most `width` wide
+  pub fn txt<T: Write>(&self, headers: &[String], buf: &mut T, width: usize) -> Result<()> {
+    let tables = create_tables(headers, self, width);
+
+    writeln!(buf, "Sample: {}", self.path).context("could not write header to file")?;
+    writeln!(buf, "SHA-256: {}", self.sha256).context("could not write header to file")?;
+
+    for ((header, table), category) in tables.iter().zip(self.suspect_imports.iter()) {
+      if !category.is_empty() {
+        writeln!(buf, "{header}:").context("could not write header to file")?;
+        writeln!(buf, "{table}").context("could not write table to file")?;
+      }
+    }
+
+    if !self.behaviors.is_empty() {
+      let mut table = Table::new(self.behaviors);
+      table.modify(Rows::new(0..), Width::wrap(width / 4).keep_words(true));
+
+      writeln!(buf, "Behaviors:").context("could not write header to file")?;
+      writeln!(buf, "{table}").context("could not write table to file")?;
+    }
+
+    if let Some(chain) = &self.chain {
+      if !chain.is_empty() {
+        writeln!(buf, "Potential attack chain:").context("could not write header to file")?;
+      }
+
+      for (i, step) in chain.iter().enumerate() {
+        let apis = step.apis.iter().map(|api| api.as_str()).collect::<Vec<_>>();
+
+        writeln!(buf, "{}. {}: the sample {}", i + 1, step.category, step.phase)
+          .context("could not write attack chain to file")?;
+        writeln!(buf, "   via {}", apis.join(", "))
+          .context("could not write attack chain to file")?;
+      }
+    }
+
+    writeln!(buf, "Score: {} ({})", self.score.value, self.score.verdict)
+      .context("could not write score to file")?;
+
+    Ok(())
+  }
+}
+
+/// Serializes [Sample] along with the headers of its categories
+struct SampleEntry<'a, 'b> {
+  headers: &'a [String],
+  sample: &'a Sample<'b>,
+}
+
+impl Serialize for SampleEntry<'_, '_> {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
     S: Serializer
   {
-    if self.headers.len() != self.suspect_imports.len() {
+    if self.headers.len() != self.sample.suspect_imports.len() {
       return Err(serde::ser::Error::custom(
           "headers and suspect_imports are different lengths"
       ));
     }
 
-    let mut map = serializer.serialize_map(Some(self.headers.len()))?;
+    let mut map = serializer.serialize_map(None)?;
+
+    map.serialize_entry("sha256", self.sample.sha256)?;
+    map.serialize_entry("score", self.sample.score)?;
 
-    for (header, category) in self.headers.iter().zip(self.suspect_imports.iter()) {
+    if self.sample.suspect_imports.iter().any(|category| !category.is_empty()) {
+      map.serialize_entry("categories", &Categories { headers: self.headers, sample: self.sample })?;
+    }
+
+    if !self.sample.behaviors.is_empty() {
+      map.serialize_entry("behaviors", self.sample.behaviors)?;
+    }
+
+    if let Some(chain) = &self.sample.chain {
+      if !chain.is_empty() {
+        map.serialize_entry("attack_chain", chain)?;
+      }
+    }
+
+    map.end()
+  }
+}
+
+/// Serializes the non-empty categories of [Sample] by their headers, apart
+/// from its other fields so that headers cannot collide with them
+struct Categories<'a, 'b> {
+  headers: &'a [String],
+  sample: &'a Sample<'b>,
+}
+
+impl Serialize for Categories<'_, '_> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer
+  {
+    let mut map = serializer.serialize_map(None)?;
+
+    for (header, category) in self.headers.iter().zip(self.sample.suspect_imports.iter()) {
       if !category.is_empty() {
         map.serialize_entry(header, category)?;
       }
@@ -141,16 +336,97 @@ impl Serialize for Output<'_> {
   }
 }
 
-impl Output<'_> {
-  /// Output to `buf` as plain text
-  pub fn txt<T: Write>(&self, buf: &mut T, args: &Args) -> Result<()> {
-    let tables = create_tables(self, args);
+/// Serializes samples of [Output] by their paths, apart from its metadata
+/// so that paths cannot collide with it
+struct Samples<'a, 'b> {
+  headers: &'a [String],
+  samples: &'a [Sample<'b>],
+}
 
-    for ((header, table), category) in tables.iter().zip(self.suspect_imports.iter()) {
-      if !category.is_empty() {
-        writeln!(buf, "{header}:").context("could not write header to file")?;
-        writeln!(buf, "{table}").context("could not write table to file")?;
+impl Serialize for Samples<'_, '_> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer
+  {
+    let mut map = serializer.serialize_map(Some(self.samples.len()))?;
+
+    for sample in self.samples {
+      map.serialize_entry(sample.path, &SampleEntry { headers: self.headers, sample })?;
+    }
+
+    map.end()
+  }
+}
+
+/// Information about the tool and API data which produced the output
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct Metadata {
+  /// Version of pescan
+  pub version: &'static str,
+  /// Date of embedded API data snapshot, if used because no cache exists
+  pub snapshot: Option<String>,
+  /// Time API data was last updated, in seconds since the Unix epoch
+  pub scraped: Option<u64>,
+  /// Credits of the sources of API data
+  pub sources: Vec<String>,
+}
+
+impl Metadata {
+  /// Collects metadata of running pescan and `cache`
+  pub fn new(cache: &Cache) -> Metadata {
+    Metadata {
+      version: env!("CARGO_PKG_VERSION"),
+      snapshot: cache.snapshot.clone(),
+      scraped: cache.last_updated(),
+      sources: cache.attributions().into_iter().cloned().collect(),
+    }
+  }
+}
+
+/// Wrapper to group headers and samples for outputting
+pub struct Output<'b> {
+  /// [Vec] of technique categories
+  pub headers: Vec<String>,
+  /// Information about the tool and API data
+  pub metadata: Metadata,
+  /// Scanned samples, in order of scanning
+  pub samples: Vec<Sample<'b>>
+}
+
+impl Serialize for Output<'_> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer
+  {
+    let mut map = serializer.serialize_map(Some(2))?;
+
+    map.serialize_entry("metadata", &self.metadata)?;
+    map.serialize_entry("samples", &Samples { headers: &self.headers, samples: &self.samples })?;
+
+    map.end()
+  }
+}
+
+/// Single CSV table of [Output]
+struct CsvSection {
+  /// File name (without extension) when writing to directory
+  file: String,
+  /// Title when writing to stdout
+  title: String,
+  /// CSV data
+  data: Vec<u8>,
+}
+
+impl Output<'_> {
+  /// Output to `buf` as plain text, with tables at most `width` wide
+  pub fn txt<T: Write>(&self, buf: &mut T, width: usize) -> Result<()> {
+    for (i, sample) in self.samples.iter().enumerate() {
+      if i > 0 {
+        writeln!(buf).context("could not write to file")?;
       }
+
+      sample.txt(&self.headers, buf, width)?;
     }
 
     Ok(())
@@ -183,83 +459,194 @@ impl Output<'_> {
     Ok(())
   }
 
-  /// Output to `path/{HEADER}.csv` as CSV
-  pub fn csv_to_file(&self, path: &Utf8PathBuf, args: &Args) -> Result<()> {
-    if path.is_dir() {
-      for (header, category) in self.headers.iter().zip(self.suspect_imports.iter()) {
-        if !category.is_empty() {
-          let file = File::create_new(path.join(format!("{header}.csv")))?;
-          let mut wtr = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(file);
-
-          let mut table_headers = vec![String::from("name")];
-          if args.info || args.all {
-            table_headers.push(String::from("info"));
-          }
-          if args.library || args.all {
-            table_headers.push(String::from("library"));
-          }
-          if args.documentation || args.all {
-            table_headers.push(String::from("documentation"));
-          }
-
-          wtr.write_record(&table_headers)?;
-
-          for import in category {
-            let success = wtr.serialize(import);
-
-            if success.is_err() {
-              wtr.write_record(std::iter::repeat_n("", table_headers.len() - import.len()))?;
-            }
-          }
-
-          wtr.flush()?;
+  /// Renders every non-empty table as CSV, with rows of all samples prefixed
+  /// by their path and SHA-256 hash
+  fn csv_sections(&self) -> Result<Vec<CsvSection>> {
+    let mut sections = Vec::new();
+
+    for (i, header) in self.headers.iter().enumerate() {
+      if self.samples.iter().all(|sample| sample.suspect_imports[i].is_empty()) {
+        continue;
+      }
+
+      let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+
+      let imports = || self.samples.iter().flat_map(|sample| &sample.suspect_imports[i]);
+
+      let mut table_headers = vec![String::from("path"), String::from("sha256"), String::from("name")];
+      if imports().any(|import| import.imported.is_some()) {
+        table_headers.push(String::from("imported"));
+      }
+      table_headers.push(String::from("import"));
+      if imports().any(|import| import.ordinal.is_some()) {
+        table_headers.push(String::from("ordinal"));
+      }
+      table_headers.push(String::from("dll"));
+      if imports().any(|import| import.info.is_some()) {
+        table_headers.push(String::from("info"));
+      }
+      if imports().any(|import| import.library.is_some()) {
+        table_headers.push(String::from("library"));
+      }
+      if imports().any(|import| import.documentation.is_some()) {
+        table_headers.push(String::from("documentation"));
+      }
+      if imports().any(|import| import.techniques.is_some()) {
+        table_headers.push(String::from("techniques"));
+      }
+      if imports().any(|import| import.sources.is_some()) {
+        table_headers.push(String::from("sources"));
+      }
+      if imports().any(|import| import.local.is_some()) {
+        table_headers.push(String::from("local"));
+      }
+      if imports().any(|import| import.unexpected.is_some()) {
+        table_headers.push(String::from("unexpected"));
+      }
+
+      wtr.write_record(&table_headers)?;
+
+      for sample in &self.samples {
+        for import in &sample.suspect_imports[i] {
+          // fields omitted from an import are left empty in its row
+          let fields = serde_json::to_value(import)?;
+          let mut record = vec![sample.path.clone(), sample.sha256.clone()];
+
+          record.extend(table_headers[2..].iter().map(|column| match &fields[column] {
+            serde_json::Value::String(value) => value.clone(),
+            serde_json::Value::Null => String::new(),
+            value => value.to_string(),
+          }));
+
+          wtr.write_record(&record)?;
         }
       }
 
-      Ok(())
-    } else {
-      Err(anyhow!("csv format requires output path to be directory"))
+      sections.push(CsvSection {
+        file: header.clone(),
+        title: header.clone(),
+        data: wtr.into_inner()?
+      });
     }
-  }
 
-  /// Output to stdout as CSV
-  pub fn csv_to_stdout(&self, args: &Args) -> Result<()> {
-    for (header, category) in self.headers.iter().zip(self.suspect_imports.iter()) {
-      if !category.is_empty() {
-        let mut wtr = csv::WriterBuilder::new()
-          .has_headers(false)
-          .from_writer(std::io::stdout());
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["path", "sha256", "score", "verdict"])?;
 
-        println!("{header}:");
-        std::io::stdout().flush()?;
+    for sample in &self.samples {
+      wtr.write_record([
+        sample.path.as_str(),
+        sample.sha256,
+        &sample.score.value.to_string(),
+        &sample.score.verdict.to_string()
+      ])?;
+    }
 
-        let mut table_headers = vec![String::from("name")];
-        if args.info || args.all {
-          table_headers.push(String::from("info"));
-        }
-        if args.library || args.all {
-          table_headers.push(String::from("library"));
-        }
-        if args.documentation || args.all {
-          table_headers.push(String::from("documentation"));
-        }
+    sections.push(CsvSection {
+      file: String::from("summary"),
+      title: String::from("Summary"),
+      data: wtr.into_inner()?
+    });
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["key", "value"])?;
+    wtr.write_record(["version", self.metadata.version])?;
+    if let Some(snapshot) = &self.metadata.snapshot {
+      wtr.write_record(["snapshot", snapshot])?;
+    }
+    if let Some(scraped) = self.metadata.scraped {
+      wtr.write_record(["scraped", &scraped.to_string()])?;
+    }
+    for source in &self.metadata.sources {
+      wtr.write_record(["source", source])?;
+    }
 
-        wtr.write_record(&table_headers)?;
+    sections.push(CsvSection {
+      file: String::from("metadata"),
+      title: String::from("Metadata"),
+      data: wtr.into_inner()?
+    });
+
+    if self.samples.iter().any(|sample| !sample.behaviors.is_empty()) {
+      let mut wtr = csv::Writer::from_writer(Vec::new());
+      wtr.write_record(["path", "sha256", "name", "severity", "attack", "description", "evidence"])?;
+
+      for sample in &self.samples {
+        for behavior in sample.behaviors {
+          wtr.write_record([
+            sample.path.as_str(),
+            sample.sha256,
+            &behavior.name,
+            &behavior.severity.to_string(),
+            behavior.attack.as_deref().unwrap_or_default(),
+            &behavior.description,
+            &behavior.evidence.join(" ")
+          ])?;
+        }
+      }
 
-        for import in category {
-          let success = wtr.serialize(import);
+      sections.push(CsvSection {
+        file: String::from("behaviors"),
+        title: String::from("Behaviors"),
+        data: wtr.into_inner()?
+      });
+    }
 
-          if success.is_err() {
-            wtr.write_record(std::iter::repeat_n("", table_headers.len() - import.len()))?;
-          }
+    if self.samples.iter().any(|sample| sample.chain.as_ref().is_some_and(|chain| !chain.is_empty())) {
+      let mut wtr = csv::Writer::from_writer(Vec::new());
+      wtr.write_record(["path", "sha256", "step", "category", "phase", "apis"])?;
+
+      for sample in &self.samples {
+        for (i, step) in sample.chain.iter().flatten().enumerate() {
+          let apis = step.apis.iter().map(|api| api.as_str()).collect::<Vec<_>>();
+
+          wtr.write_record([
+            sample.path.as_str(),
+            sample.sha256,
+            &(i + 1).to_string(),
+            &step.category,
+            step.phase,
+            &apis.join(" ")
+          ])?;
         }
+      }
 
-        wtr.flush()?;
-        println!();
+      sections.push(CsvSection {
+        file: String::from("attack_chain"),
+        title: String::from("Attack chain"),
+        data: wtr.into_inner()?
+      });
+    }
+
+    Ok(sections)
+  }
+
+  /// Output to `path/{HEADER}.csv` as CSV
+  pub fn csv_to_file(&self, path: &Utf8PathBuf) -> Result<()> {
+    if path.is_dir() {
+      for section in self.csv_sections()? {
+        let mut file = File::create_new(path.join(format!("{}.csv", section.file)))?;
+        file.write_all(&section.data)?;
       }
+
+      Ok(())
+    } else {
+      Err(anyhow!("csv format requires output path to be directory"))
     }
+  }
+
+  /// Output to stdout as CSV
+  pub fn csv_to_stdout(&self) -> Result<()> {
+    let mut stdout = std::io::stdout();
+
+    for section in self.csv_sections()? {
+      writeln!(stdout, "{}:", section.title)?;
+      stdout.write_all(&section.data)?;
+      writeln!(stdout)?;
+    }
+
+    stdout.flush()?;
 
     Ok(())
   }
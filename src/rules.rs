@@ -0,0 +1,449 @@
+//! Provides [Rule] struct and [Expr] enum for detecting named behaviours
+//! from combinations of imported APIs, along with a set of built-in rules
+//! and loading of user-defined rule files.
+
+use serde::{Serialize, Deserialize};
+use tabled::{Tabled, derive::display};
+use anyhow::{Result, Context, bail};
+use camino::Utf8Path;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+use crate::output::format_list;
+
+/// Severity of a detected behaviour
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  /// Informational, common in benign software
+  Info,
+  /// Weak indicator
+  Low,
+  /// Noteworthy indicator
+  #[default]
+  Medium,
+  /// Strong indicator
+  High,
+  /// Almost exclusively seen in malware
+  Critical,
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Severity::Info => "info",
+      Severity::Low => "low",
+      Severity::Medium => "medium",
+      Severity::High => "high",
+      Severity::Critical => "critical",
+    })
+  }
+}
+
+/// Boolean expression over imported API names and DLLs
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expr {
+  /// Matches if API is imported
+  Api(String),
+  /// Matches if DLL is imported (case insensitive)
+  Dll(String),
+  /// Matches if sub-expression does not match
+  Not(Box<Expr>),
+  /// Matches if every sub-expression matches
+  All(Vec<Expr>),
+  /// Matches if any sub-expression matches
+  Any(Vec<Expr>),
+  /// Matches if at least `n` sub-expressions match
+  AtLeast {
+    /// Minimum number of matching sub-expressions
+    n: usize,
+    /// Sub-expressions to count matches of
+    of: Vec<Expr>
+  },
+}
+
+impl Expr {
+  /// Evaluates expression against imported APIs and DLLs, pushing names which
+  /// caused the match to `evidence`. Evidence of failed sub-expressions is discarded.
+  fn eval(&self, imports: &Imports, evidence: &mut Vec<String>) -> bool {
+    let start = evidence.len();
+
+    let matched = match self {
+      Expr::Api(name) => {
+        let found = imports.apis.contains(name);
+        if found && !evidence.contains(name) {
+          evidence.push(name.clone());
+        }
+        found
+      },
+      Expr::Dll(name) => {
+        let name = name.to_ascii_lowercase();
+        let found = imports.dlls.contains(&name);
+        if found && !evidence.contains(&name) {
+          evidence.push(name);
+        }
+        found
+      },
+      Expr::Not(expr) => {
+        let matched = !expr.eval(imports, evidence);
+        evidence.truncate(start);
+        matched
+      },
+      Expr::All(exprs) => exprs.iter()
+        .fold(true, |acc, expr| expr.eval(imports, evidence) && acc),
+      Expr::Any(exprs) => exprs.iter()
+        .fold(false, |acc, expr| expr.eval(imports, evidence) || acc),
+      Expr::AtLeast { n, of } => of.iter()
+        .filter(|expr| expr.eval(imports, evidence)).count() >= *n,
+    };
+
+    if !matched {
+      evidence.truncate(start);
+    }
+
+    matched
+  }
+}
+
+/// Imported API names and lowercase DLL names of a sample
+pub struct Imports<'a> {
+  /// Imported API names
+  pub apis: &'a HashSet<String>,
+  /// Imported DLL names, in lowercase
+  pub dlls: &'a HashSet<String>,
+}
+
+/// Named behaviour detected by a combination of APIs
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+  /// Name of behaviour
+  pub name: String,
+  /// Summary of behaviour
+  #[serde(default)]
+  pub description: String,
+  /// Severity of behaviour
+  #[serde(default)]
+  pub severity: Severity,
+  /// MITRE ATT&CK technique ID
+  pub attack: Option<String>,
+  /// Expression which must match for behaviour to be reported
+  pub condition: Expr,
+}
+
+impl Rule {
+  /// Evaluates rule against `imports`, returning [Behavior] with evidence
+  /// if the condition matches
+  pub fn evaluate(&self, imports: &Imports) -> Option<Behavior> {
+    let mut evidence = Vec::new();
+
+    if self.condition.eval(imports, &mut evidence) {
+      Some(Behavior {
+        name: self.name.clone(),
+        severity: self.severity,
+        attack: self.attack.clone(),
+        description: self.description.clone(),
+        evidence
+      })
+    } else {
+      None
+    }
+  }
+}
+
+/// Behaviour detected in sample, with the APIs that justify it
+#[serde_with::skip_serializing_none]
+#[derive(Serialize, Tabled)]
+pub struct Behavior {
+  /// Name of behaviour
+  pub name: String,
+  /// Severity of behaviour
+  pub severity: Severity,
+  /// MITRE ATT&CK technique ID
+  #[tabled(display("display::option", ""))]
+  pub attack: Option<String>,
+  /// Summary of behaviour
+  pub description: String,
+  /// Imported APIs and DLLs which matched the rule
+  #[tabled(display("format_list"))]
+  pub evidence: Vec<String>,
+}
+
+/// Evaluates all `rules` against `imports`, returning matched behaviours
+pub fn evaluate(rules: &[Rule], imports: &Imports) -> Vec<Behavior> {
+  rules.iter().filter_map(|rule| rule.evaluate(imports)).collect()
+}
+
+/// Layout of user-defined rule files
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+  rules: Vec<Rule>,
+}
+
+/// Load user-defined rules from a YAML, TOML or JSON file, chosen by
+/// the file extension
+pub fn load(path: &Utf8Path) -> Result<Vec<Rule>> {
+  let contents = fs::read_to_string(path)
+    .with_context(|| format!("could not read rule file {path}"))?;
+
+  let file: RuleFile = match path.extension() {
+    // serde_yml only accepts `!tag` syntax for enums, go through
+    // serde_json to allow the same `{ all: [...] }` syntax as other formats
+    Some("yaml" | "yml") => serde_yml::from_str::<serde_json::Value>(&contents)
+      .map_err(anyhow::Error::from)
+      .and_then(|value| Ok(serde_json::from_value(value)?))
+      .with_context(|| format!("invalid rule file {path}"))?,
+    Some("toml") => toml::from_str(&contents)
+      .with_context(|| format!("invalid rule file {path}"))?,
+    Some("json") => serde_json::from_str(&contents)
+      .with_context(|| format!("invalid rule file {path}"))?,
+    _ => bail!("unsupported rule file {path}, expected .yaml, .yml, .toml or .json")
+  };
+
+  Ok(file.rules)
+}
+
+fn api(name: &str) -> Expr {
+  Expr::Api(name.to_owned())
+}
+
+fn any_api(names: &[&str]) -> Expr {
+  Expr::Any(names.iter().map(|name| api(name)).collect())
+}
+
+fn rule<const N: usize>(
+  name: &str,
+  severity: Severity,
+  attack: &str,
+  description: &str,
+  all: [Expr; N]
+) -> Rule {
+  Rule {
+    name: name.to_owned(),
+    description: description.to_owned(),
+    severity,
+    attack: Some(attack.to_owned()),
+    condition: Expr::All(all.into())
+  }
+}
+
+/// Built-in rules for well known technique combinations
+pub fn builtin() -> Vec<Rule> {
+  vec![
+    rule("Remote thread injection", Severity::High, "T1055",
+      "allocates and writes memory in another process, then starts a thread in it",
+      [
+        api("VirtualAllocEx"),
+        api("WriteProcessMemory"),
+        any_api(&["CreateRemoteThread", "CreateRemoteThreadEx", "NtCreateThreadEx", "RtlCreateUserThread"])
+      ]),
+    rule("APC injection", Severity::High, "T1055.004",
+      "writes memory in another process and queues an APC to execute it",
+      [
+        any_api(&["VirtualAllocEx", "NtAllocateVirtualMemory"]),
+        any_api(&["WriteProcessMemory", "NtWriteVirtualMemory"]),
+        any_api(&["QueueUserAPC", "NtQueueApcThread"])
+      ]),
+    rule("Process hollowing", Severity::High, "T1055.012",
+      "creates a process, unmaps its image and replaces it with foreign code",
+      [
+        any_api(&["CreateProcessA", "CreateProcessW", "CreateProcessInternalW"]),
+        any_api(&["NtUnmapViewOfSection", "ZwUnmapViewOfSection"]),
+        api("WriteProcessMemory"),
+        any_api(&["SetThreadContext", "Wow64SetThreadContext"]),
+        api("ResumeThread")
+      ]),
+    rule("Thread execution hijacking", Severity::High, "T1055.003",
+      "suspends a thread and redirects its execution context",
+      [
+        api("SuspendThread"),
+        any_api(&["GetThreadContext", "Wow64GetThreadContext"]),
+        any_api(&["SetThreadContext", "Wow64SetThreadContext"]),
+        api("ResumeThread")
+      ]),
+    rule("Section mapping injection", Severity::High, "T1055",
+      "shares code with another process through a mapped section",
+      [
+        any_api(&["NtCreateSection", "ZwCreateSection"]),
+        any_api(&["NtMapViewOfSection", "ZwMapViewOfSection"])
+      ]),
+    rule("LSASS credential dumping", Severity::Critical, "T1003.001",
+      "opens a process and writes its memory to a minidump",
+      [
+        api("OpenProcess"),
+        api("MiniDumpWriteDump")
+      ]),
+    rule("Token privilege escalation", Severity::Medium, "T1134",
+      "enables privileges on its own access token",
+      [
+        api("OpenProcessToken"),
+        any_api(&["LookupPrivilegeValueA", "LookupPrivilegeValueW"]),
+        api("AdjustTokenPrivileges")
+      ]),
+    rule("Process enumeration", Severity::Low, "T1057",
+      "walks the list of running processes",
+      [
+        api("CreateToolhelp32Snapshot"),
+        any_api(&["Process32First", "Process32FirstW"]),
+        any_api(&["Process32Next", "Process32NextW"])
+      ]),
+    rule("Keylogging", Severity::High, "T1056.001",
+      "hooks or polls the keyboard state",
+      [
+        Expr::AtLeast {
+          n: 2,
+          of: vec![
+            any_api(&["SetWindowsHookExA", "SetWindowsHookExW"]),
+            api("GetAsyncKeyState"),
+            api("GetKeyState"),
+            api("GetKeyboardState"),
+            api("GetForegroundWindow"),
+            any_api(&["MapVirtualKeyA", "MapVirtualKeyW"])
+          ]
+        }
+      ]),
+    rule("Screen capture", Severity::Medium, "T1113",
+      "copies the contents of the screen into a bitmap",
+      [
+        any_api(&["GetDC", "GetWindowDC", "GetDesktopWindow"]),
+        api("CreateCompatibleBitmap"),
+        any_api(&["BitBlt", "StretchBlt"])
+      ]),
+    rule("Download and execute", Severity::High, "T1105",
+      "downloads a payload and executes it",
+      [
+        Expr::Any(vec![
+          any_api(&["URLDownloadToFileA", "URLDownloadToFileW"]),
+          Expr::All(vec![
+            any_api(&["InternetOpenUrlA", "InternetOpenUrlW", "HttpSendRequestA", "HttpSendRequestW"]),
+            api("InternetReadFile")
+          ])
+        ]),
+        any_api(&["ShellExecuteA", "ShellExecuteW", "ShellExecuteExA", "ShellExecuteExW",
+          "CreateProcessA", "CreateProcessW", "WinExec"])
+      ]),
+    rule("File encryption", Severity::Critical, "T1486",
+      "enumerates files and encrypts their contents",
+      [
+        any_api(&["FindFirstFileA", "FindFirstFileW", "FindFirstFileExA", "FindFirstFileExW"]),
+        any_api(&["FindNextFileA", "FindNextFileW"]),
+        any_api(&["CryptEncrypt", "BCryptEncrypt"])
+      ]),
+    rule("Debugger detection", Severity::Medium, "T1622",
+      "checks for attached debuggers using several methods",
+      [
+        Expr::AtLeast {
+          n: 2,
+          of: vec![
+            api("IsDebuggerPresent"),
+            api("CheckRemoteDebuggerPresent"),
+            api("NtQueryInformationProcess"),
+            // OutputDebugString is left out, as the C runtime imports it
+            // along with IsDebuggerPresent
+            api("NtSetInformationThread")
+          ]
+        }
+      ]),
+    rule("Dynamic API resolution", Severity::Low, "T1027.007",
+      "loads modules and resolves functions at runtime",
+      [
+        any_api(&["LoadLibraryA", "LoadLibraryW", "LoadLibraryExA", "LoadLibraryExW",
+          "GetModuleHandleA", "GetModuleHandleW"]),
+        api("GetProcAddress")
+      ]),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn eval(expr: &Expr, apis: &[&str]) -> Option<Vec<String>> {
+    let apis = apis.iter().map(|api| api.to_string()).collect();
+    let imports = Imports { apis: &apis, dlls: &HashSet::new() };
+    let mut evidence = Vec::new();
+
+    expr.eval(&imports, &mut evidence).then_some(evidence)
+  }
+
+  #[test]
+  fn at_least() {
+    let expr = |n| Expr::AtLeast { n, of: vec![api("A"), api("B"), api("C")] };
+
+    assert_eq!(eval(&expr(2), &["A", "C"]), Some(vec![String::from("A"), String::from("C")]));
+    assert_eq!(eval(&expr(3), &["A", "C"]), None);
+    assert_eq!(eval(&expr(0), &[]), Some(vec![]));
+  }
+
+  #[test]
+  fn not() {
+    assert_eq!(eval(&Expr::Not(Box::new(api("B"))), &["A"]), Some(vec![]));
+    assert_eq!(eval(&Expr::Not(Box::new(api("A"))), &["A"]), None);
+
+    // neither negated matches nor failed sub-expressions are evidence
+    let expr = Expr::All(vec![api("A"), Expr::Not(Box::new(Expr::All(vec![api("A"), api("B")])))]);
+    assert_eq!(eval(&expr, &["A"]), Some(vec![String::from("A")]));
+  }
+
+  /// Writes `contents` to a temporary rule file named `name` and loads it
+  fn load_str(name: &str, contents: &str) -> Result<Vec<Rule>> {
+    let path = std::env::temp_dir().join(format!("pescan-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+
+    let rules = load(Utf8Path::from_path(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+    rules
+  }
+
+  #[test]
+  fn load_formats() {
+    let yaml = "\
+rules:
+  - name: Injection
+    severity: high
+    attack: T1055
+    condition:
+      all:
+        - api: VirtualAllocEx
+        - at_least: { n: 1, of: [ { api: CreateRemoteThread }, { dll: ntdll.dll } ] }
+";
+    let toml = r#"
+[[rules]]
+name = "Injection"
+severity = "high"
+attack = "T1055"
+condition = { all = [
+  { api = "VirtualAllocEx" },
+  { at_least = { n = 1, of = [ { api = "CreateRemoteThread" }, { dll = "ntdll.dll" } ] } },
+] }
+"#;
+
+    let apis = ["VirtualAllocEx", "CreateRemoteThread"].map(String::from).into();
+    let imports = Imports { apis: &apis, dlls: &HashSet::new() };
+
+    for (name, contents) in [("rules.yaml", yaml), ("rules.toml", toml)] {
+      let rules = load_str(name, contents).unwrap();
+      assert_eq!(rules.len(), 1, "{name}");
+
+      let rule = &rules[0];
+      assert_eq!((rule.name.as_str(), rule.attack.as_deref()), ("Injection", Some("T1055")), "{name}");
+      assert!(rule.severity == Severity::High, "{name}");
+      assert_eq!(rule.description, "", "{name}");
+      assert_eq!(rule.evaluate(&imports).map(|behavior| behavior.evidence), Some(vec![
+        String::from("VirtualAllocEx"), String::from("CreateRemoteThread"),
+      ]), "{name}");
+    }
+  }
+
+  #[test]
+  fn load_rejects_unknown_fields() {
+    assert!(load_str("severty.yaml", "rules:\n  - name: X\n    severty: high\n    condition: { api: A }\n").is_err());
+    assert!(load_str("atack.toml", "[[rules]]\nname = \"X\"\natack = \"T1055\"\ncondition = { api = \"A\" }\n").is_err());
+    assert!(load_str("extra.json", r#"{ "rules": [], "version": 1 }"#).is_err());
+  }
+}
@@ -0,0 +1,531 @@
+//! Provides [Scanner] struct for scanning samples into owned [Report]s,
+//! [scan_file] for scanning archives member by member and [scan_all] for
+//! scanning many samples in parallel.
+
+use anyhow::{Result, Context, anyhow};
+use goblin::Object;
+use sha2::{Sha256, Digest};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use std::{fs, thread};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_set::HashSet;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::archive;
+use crate::cache::{Cache, LOCAL};
+use crate::exit::{Failure, WithFailure};
+use crate::imports::{self, ImportKind};
+use crate::input::Input;
+use crate::rules::{self, Behavior, Imports, Rule};
+use crate::score::{Score, Weight};
+
+/// Suspect API imported by a sample, with its details from the cache
+#[derive(Clone, Default, Serialize)]
+pub struct Hit {
+  /// Name of API in the cache
+  pub name: String,
+  /// Name sample imports API by, which differs from `name` if matched by
+  /// its [canonical](imports::canonical) name
+  pub imported: String,
+  /// How sample imports API
+  pub import: ImportKind,
+  /// Ordinal sample imports API by, if not imported by name
+  pub ordinal: Option<u16>,
+  /// DLL sample imports API from
+  pub dll: String,
+  /// Whether `dll` is not the library which documents API, see
+  /// [imports::expected_library]
+  pub unexpected: bool,
+  /// Summary of API functionality
+  pub info: String,
+  /// Library which documents API
+  pub library: String,
+  /// Link to API documentation
+  pub documentation: String,
+  /// MITRE ATT&CK technique IDs of API
+  pub techniques: Vec<String>,
+  /// Whether API was defined in a local overlay rather than scraped
+  pub local: bool,
+  /// Names of the sources API came from
+  pub sources: Vec<String>,
+}
+
+/// Suspect imports of a sample in a single technique category
+#[derive(Clone, Serialize)]
+pub struct Category {
+  /// Name of technique category
+  pub name: String,
+  /// Suspect imports, sorted by name
+  pub hits: Vec<Hit>,
+}
+
+/// Owned results of scanning a single sample
+#[derive(Serialize)]
+pub struct Report {
+  /// SHA-256 hash of sample, in hexadecimal
+  pub sha256: String,
+  /// Suspect imports of every technique category, in the order of
+  /// [Cache::headers]
+  pub categories: Vec<Category>,
+  /// Behaviours detected from combinations of APIs
+  pub behaviors: Vec<Behavior>,
+  /// Suspicion score and verdict
+  pub score: Score,
+}
+
+/// Scans samples for suspect imports in a loaded [Cache] and for behaviours
+/// matching a set of rules
+pub struct Scanner<'c> {
+  cache: &'c Cache,
+  apis: Vec<HashSet<String>>,
+  /// APIs of each category by canonical name, unless matching exactly
+  canonical: Option<Vec<HashMap<String, Vec<String>>>>,
+  /// Whether to match strings of samples against API names
+  strings: bool,
+  rules: Vec<Rule>,
+  weights: Vec<Weight>,
+}
+
+impl<'c> Scanner<'c> {
+  /// Creates scanner for `cache` with [rules::builtin] rules and
+  /// [DEFAULT_WEIGHTS](crate::score::DEFAULT_WEIGHTS)
+  pub fn new(cache: &'c Cache) -> Scanner<'c> {
+    let apis = cache.get_apis();
+    let canonical = apis.iter()
+      .map(|category| {
+        let mut canonical = HashMap::<String, Vec<String>>::new();
+        for name in category {
+          canonical.entry(imports::canonical(name)).or_default().push(name.clone());
+        }
+        canonical
+      })
+      .collect();
+
+    Scanner {
+      cache,
+      apis,
+      canonical: Some(canonical),
+      strings: true,
+      rules: rules::builtin(),
+      weights: Vec::new(),
+    }
+  }
+
+  /// Evaluates `rules` in addition to the current rules
+  pub fn rules(mut self, rules: impl IntoIterator<Item = Rule>) -> Scanner<'c> {
+    self.rules.extend(rules);
+    self
+  }
+
+  /// Matches imported names exactly if `exact`, rather than also by their
+  /// [canonical](imports::canonical) names
+  pub fn exact(mut self, exact: bool) -> Scanner<'c> {
+    if exact {
+      self.canonical = None;
+    }
+    self
+  }
+
+  /// Matches [strings](imports::strings) of samples which are not imported
+  /// against API names if `strings`, as APIs resolved at runtime. Only APIs
+  /// which no import matches are matched by strings.
+  pub fn strings(mut self, strings: bool) -> Scanner<'c> {
+    self.strings = strings;
+    self
+  }
+
+  /// Scores categories with `weights`, which take precedence over the
+  /// default weights
+  pub fn weights(mut self, weights: &[Weight]) -> Scanner<'c> {
+    self.weights.extend_from_slice(weights);
+    self
+  }
+
+  /// Maps names of `candidates` to cached names of category `i` in
+  /// `matched`, keeping names which are already matched. Exact matches take
+  /// precedence over matches of canonical names, and each candidate matches
+  /// at most one cached name, preferring variants which differ only in
+  /// their `A`/`W` suffix over `Ex` variants.
+  fn match_names<'s, 'n>(
+    &'s self,
+    i: usize,
+    candidates: &'n HashSet<String>,
+    matched: &mut BTreeMap<&'s String, &'n String>
+  ) {
+    let category = &self.apis[i];
+    let mut unmatched = Vec::new();
+
+    for candidate in candidates {
+      match category.get(candidate) {
+        Some(name) => {
+          matched.entry(name).or_insert(candidate);
+        },
+        None => unmatched.push(candidate),
+      }
+    }
+
+    let Some(canonical) = &self.canonical else {
+      return;
+    };
+    unmatched.sort();
+
+    for candidate in unmatched {
+      let variant = imports::variant(candidate);
+      let name = canonical[i].get(&imports::canonical(candidate)).into_iter().flatten()
+        .min_by_key(|name| (imports::variant(name) != variant, *name));
+
+      if let Some(name) = name {
+        matched.entry(name).or_insert(candidate);
+      }
+    }
+  }
+
+  /// Cached names of category `i` mapped to the names of `imports` or, for
+  /// cached names which no import matches, of `strings`, so that strings
+  /// never take over imported APIs
+  fn matches<'s, 'n>(
+    &'s self,
+    i: usize,
+    imports: &'n HashSet<String>,
+    strings: &'n HashSet<String>
+  ) -> BTreeMap<&'s String, &'n String> {
+    let mut matched = BTreeMap::new();
+    self.match_names(i, imports, &mut matched);
+    self.match_names(i, strings, &mut matched);
+
+    matched
+  }
+
+  /// Scans `sample_buffer`, which must be a PE file
+  pub fn scan(&self, sample_buffer: &[u8]) -> Result<Report> {
+    let sha256 = format!("{:x}", Sha256::digest(sample_buffer));
+
+    match Object::parse(sample_buffer)
+      .context("could not parse sample")
+      .failure(Failure::Parse)?
+    {
+      Object::PE(pe) => {
+        let imported = imports::collect(&pe, sample_buffer);
+        let dlls = pe.libraries.iter().copied()
+          .chain(imported.iter().map(|import| import.dll.as_str()))
+          .map(|dll| dll.to_ascii_lowercase()).collect::<HashSet<String>>();
+
+        std::mem::drop(pe);
+
+        let imports = imported.iter().map(|import| import.name.clone()).collect::<HashSet<String>>();
+        let behaviors = rules::evaluate(&self.rules, &Imports { apis: &imports, dlls: &dlls });
+
+        let strings = if self.strings { imports::strings(sample_buffer) } else { HashSet::new() }
+          .into_iter()
+          .filter(|name| !imports.contains(name))
+          .collect::<HashSet<String>>();
+        let dynamic = strings.iter()
+          .map(|name| imports::Import { name: name.clone(), dll: String::new(), kind: ImportKind::Dynamic, ordinal: None })
+          .collect::<Vec<_>>();
+
+        // APIs imported several ways are labelled by the most ordinary one,
+        // static before delay-load and by name before by ordinal
+        let mut labels: HashMap<&str, &imports::Import> = HashMap::new();
+        for import in imported.iter().chain(&dynamic) {
+          labels.entry(&import.name)
+            .and_modify(|label| if (import.kind, import.ordinal.is_some()) < (label.kind, label.ordinal.is_some()) {
+              *label = import;
+            })
+            .or_insert(import);
+        }
+
+        let mut categories = Vec::<Category>::with_capacity(self.apis.len());
+
+        for (i, header) in self.cache.headers.iter().enumerate() {
+          let hits = self.matches(i, &imports, &strings).into_iter()
+            .map(|(name, imported)| (name, imported, labels[imported.as_str()]))
+            .map(|(name, imported, import)| match self.cache.get_api(i, name) {
+              Some(api) => Hit {
+                name: name.clone(),
+                imported: imported.clone(),
+                import: import.kind,
+                ordinal: import.ordinal,
+                dll: import.dll.clone(),
+                unexpected: !imports::expected_library(&import.dll, &api.library),
+                info: api.info.clone(),
+                library: api.library.clone(),
+                documentation: api.documentation.clone(),
+                techniques: api.techniques.clone(),
+                local: api.sources.iter().any(|source| source == LOCAL),
+                sources: api.sources.clone(),
+              },
+              None => Hit {
+                name: name.clone(),
+                imported: imported.clone(),
+                import: import.kind,
+                ordinal: import.ordinal,
+                dll: import.dll.clone(),
+                ..Hit::default()
+              },
+            })
+            .collect();
+
+          categories.push(Category { name: header.clone(), hits });
+        }
+
+        let score = Score::compute(&categories, &self.weights);
+
+        Ok(Report { sha256, categories, behaviors, score })
+      },
+      _ => {
+        Err(Failure::Parse.tag(anyhow!("invalid file type, only PE files are supported")))
+      }
+    }
+  }
+}
+
+/// Report of a sample scanned from the command line
+pub struct Scan {
+  /// Path of sample, with archive members as `archive:member`
+  pub path: String,
+  /// Results of scanning sample
+  pub report: Report,
+}
+
+/// File or archive member which was not scanned
+pub struct Skipped {
+  /// Path of file, with archive members as `archive:member`
+  pub path: String,
+  /// Why file was not scanned
+  pub reason: String,
+}
+
+/// Reports of a file or the members of an archive, along with members which
+/// were skipped
+#[derive(Default)]
+pub struct Scanned {
+  /// Reports of samples, in the order they were found
+  pub scans: Vec<Scan>,
+  /// Files which were skipped, in the order they were found
+  pub skipped: Vec<Skipped>,
+}
+
+impl Scanned {
+  fn skip(&mut self, path: String, reason: impl ToString) {
+    self.skipped.push(Skipped { path, reason: reason.to_string() });
+  }
+}
+
+/// Scans `sample_buffer`, or every member if it is a ZIP or 7z archive,
+/// decrypted with `password` where needed. Files and archives which fail to
+/// parse are skipped if they were `discovered` in a directory or archive,
+/// and listed in [Scanned::skipped] if they appear to be PE files or
+/// archives.
+pub fn scan_file(
+  path: String,
+  sample_buffer: &[u8],
+  discovered: bool,
+  scanner: &Scanner,
+  password: &str
+) -> Result<Scanned> {
+  let mut scanned = Scanned::default();
+  scan_nested(path, sample_buffer, discovered, 0, scanner, password, &mut scanned)?;
+
+  Ok(scanned)
+}
+
+/// Scans `sample_buffer` with [scan_file] inside `depth` archives into
+/// `scanned`
+fn scan_nested(
+  path: String,
+  sample_buffer: &[u8],
+  discovered: bool,
+  depth: usize,
+  scanner: &Scanner,
+  password: &str,
+  scanned: &mut Scanned
+) -> Result<()> {
+  if let Some(kind) = archive::Kind::detect(sample_buffer) {
+    if depth >= archive::MAX_DEPTH {
+      scanned.skip(path, format!("archives nested more than {} deep", archive::MAX_DEPTH));
+      return Ok(());
+    }
+
+    let result = archive::for_each_member(sample_buffer, kind, password, |member| {
+      let path = format!("{path}:{}", member.name);
+
+      match member.data {
+        Some(data) => scan_nested(path, &data, true, depth + 1, scanner, password, scanned)?,
+        None => scanned.skip(path, format!(
+          "larger than {} MiB", archive::MAX_MEMBER_SIZE / (1024 * 1024)
+        )),
+      }
+
+      Ok(())
+    });
+
+    match result {
+      Ok(()) => (),
+      Err(e) if discovered => scanned.skip(path, e.root_cause()),
+      Err(e) => return Err(e)
+        .with_context(|| format!("could not read archive {path}"))
+        .failure(Failure::Parse),
+    }
+
+    return Ok(());
+  }
+
+  match scanner.scan(sample_buffer) {
+    Ok(report) => scanned.scans.push(Scan { path, report }),
+    Err(e) if discovered => {
+      // silently skip files which are not PE files at all
+      if sample_buffer.starts_with(b"MZ") {
+        scanned.skip(path, e.root_cause());
+      }
+    },
+    Err(e) => return Err(e),
+  }
+
+  Ok(())
+}
+
+/// Reads and scans `input` with [scan_file]
+fn scan_input(
+  input: &Input,
+  scanner: &Scanner,
+  password: &str
+) -> Result<Scanned> {
+  let sample_buffer = fs::read(&input.path)
+    .with_context(|| format!("could not read sample {}", input.path))
+    .failure(Failure::Parse)?;
+
+  scan_file(input.path.to_string(), &sample_buffer, input.discovered, scanner, password)
+}
+
+/// Scans `inputs` on up to `jobs` worker threads which share `scanner`.
+/// Scans are passed to `on_scan` in the order of `inputs` as soon as they and
+/// all preceding scans are done, along with the overall progress bar.
+/// Skipped files are passed to `on_skip` before the scans of their input.
+pub fn scan_all<F, S>(
+  inputs: &[Input],
+  jobs: usize,
+  scanner: &Scanner,
+  password: &str,
+  mut on_scan: F,
+  mut on_skip: S
+) -> Result<()>
+where
+  F: FnMut(Scan, &ProgressBar) -> Result<()>,
+  S: FnMut(Skipped, &ProgressBar)
+{
+  let bar = if inputs.len() > 1 {
+    ProgressBar::new(inputs.len().try_into()?)
+  } else {
+    ProgressBar::hidden()
+  };
+  bar.set_style(
+    ProgressStyle::with_template("{spinner} {elapsed_precise} [{bar:40}] {pos}/{len} {msg}")?
+    .progress_chars("=>-")
+  );
+
+  let next = AtomicUsize::new(0);
+  let (tx, rx) = mpsc::channel::<(usize, Result<Scanned>)>();
+
+  let result = thread::scope(|scope| {
+    for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+      let tx = tx.clone();
+      let next = &next;
+
+      scope.spawn(move || loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        if i >= inputs.len() {
+          break;
+        }
+
+        // receiver is gone if an earlier sample failed
+        if tx.send((i, scan_input(&inputs[i], scanner, password))).is_err() {
+          break;
+        }
+      });
+    }
+
+    drop(tx);
+
+    let mut pending = BTreeMap::new();
+    let mut position = 0;
+
+    for (i, result) in rx {
+      bar.inc(1);
+      bar.set_message(inputs[i].path.to_string());
+      pending.insert(i, result);
+
+      while let Some(result) = pending.remove(&position) {
+        let scanned = result?;
+        for skipped in scanned.skipped {
+          on_skip(skipped, &bar);
+        }
+        for scan in scanned.scans {
+          on_scan(scan, &bar)?;
+        }
+        position += 1;
+      }
+    }
+
+    Ok(())
+  });
+
+  bar.finish_and_clear();
+
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cache::PortableFormat;
+
+  /// Loads a cache with a single category of APIs named `names`
+  fn cache(names: &[&str]) -> Cache {
+    let apis = names.iter()
+      .map(|name| serde_json::json!({ "name": name }))
+      .collect::<Vec<_>>();
+    let portable = serde_json::json!({ "categories": [{ "name": "Category", "apis": apis }] });
+
+    Cache::import(&portable.to_string(), PortableFormat::JSON).unwrap()
+  }
+
+  /// Matches `imports` and `strings` against the single category of
+  /// `scanner`
+  fn matches(scanner: &Scanner, imports: &[&str], strings: &[&str]) -> Vec<(String, String)> {
+    let set = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<HashSet<_>>();
+    let (imports, strings) = (set(imports), set(strings));
+
+    scanner.matches(0, &imports, &strings).into_iter()
+      .map(|(name, matched)| (name.clone(), matched.clone()))
+      .collect()
+  }
+
+  #[test]
+  fn one_match_per_import() {
+    let cache = cache(&["GetModuleHandleA", "GetModuleHandleExA"]);
+    let scanner = Scanner::new(&cache);
+
+    assert_eq!(matches(&scanner, &["GetModuleHandleW"], &[]), [
+      (String::from("GetModuleHandleA"), String::from("GetModuleHandleW")),
+    ]);
+    assert_eq!(matches(&scanner, &["GetModuleHandleExW"], &[]), [
+      (String::from("GetModuleHandleExA"), String::from("GetModuleHandleExW")),
+    ]);
+    assert!(matches(&scanner.exact(true), &["GetModuleHandleW"], &[]).is_empty());
+  }
+
+  #[test]
+  fn imports_before_strings() {
+    let cache = cache(&["CreateProcessA", "VirtualAlloc"]);
+    let scanner = Scanner::new(&cache);
+
+    // a string naming the exact API must not take over the imported variant
+    assert_eq!(matches(&scanner, &["CreateProcessW"], &["CreateProcessA", "VirtualAlloc"]), [
+      (String::from("CreateProcessA"), String::from("CreateProcessW")),
+      (String::from("VirtualAlloc"), String::from("VirtualAlloc")),
+    ]);
+  }
+}
@@ -0,0 +1,122 @@
+//! Provides [Score] struct for computing a suspicion score and [Verdict]
+//! of a sample from the number of matched APIs per technique category.
+
+use serde::Serialize;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::imports::ImportKind;
+use crate::scan::Category;
+
+/// Default weight of each technique category
+pub const DEFAULT_WEIGHTS: [(&str, f64); 8] = [
+  ("Enumeration", 1.0),
+  ("Injection", 4.0),
+  ("Evasion", 3.0),
+  ("Spying", 3.0),
+  ("Internet", 2.0),
+  ("Anti-Debugging", 2.0),
+  ("Ransomware", 4.0),
+  ("Helper", 0.5),
+];
+
+/// Weight of categories not found in [DEFAULT_WEIGHTS]
+const FALLBACK_WEIGHT: f64 = 1.0;
+
+/// Minimum score for a [Verdict::Suspicious] verdict
+pub const SUSPICIOUS: f64 = 10.0;
+/// Minimum score for a [Verdict::LikelyMalicious] verdict
+pub const LIKELY_MALICIOUS: f64 = 25.0;
+
+/// Weight of a technique category, parsed from `CATEGORY=WEIGHT`
+#[derive(Clone)]
+pub struct Weight {
+  /// Technique category (case insensitive)
+  pub category: String,
+  /// Score added per matched API
+  pub weight: f64,
+}
+
+impl FromStr for Weight {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (category, weight) = s.split_once('=')
+      .ok_or_else(|| format!("expected CATEGORY=WEIGHT, found `{s}`"))?;
+
+    let weight = weight.trim().parse::<f64>()
+      .map_err(|e| format!("invalid weight `{weight}`: {e}"))?;
+
+    if !weight.is_finite() || weight < 0.0 {
+      return Err(format!("invalid weight `{weight}`, expected a finite non-negative number"));
+    }
+
+    Ok(Weight { category: category.trim().to_owned(), weight })
+  }
+}
+
+/// Triage verdict derived from [Score]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+  /// Score below [SUSPICIOUS]
+  Clean,
+  /// Score below [LIKELY_MALICIOUS]
+  Suspicious,
+  /// Score of at least [LIKELY_MALICIOUS]
+  LikelyMalicious,
+}
+
+impl fmt::Display for Verdict {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Verdict::Clean => "clean",
+      Verdict::Suspicious => "suspicious",
+      Verdict::LikelyMalicious => "likely malicious",
+    })
+  }
+}
+
+/// Suspicion score of a sample
+#[derive(Serialize)]
+pub struct Score {
+  /// Sum of imported APIs multiplied by the weight of their category
+  pub value: f64,
+  /// Verdict derived from `value`
+  pub verdict: Verdict,
+}
+
+impl Score {
+  /// Computes score from suspect imports per category, not counting APIs
+  /// which are only named by strings. Weights in `overrides` take
+  /// precedence over [DEFAULT_WEIGHTS].
+  pub fn compute(categories: &[Category], overrides: &[Weight]) -> Score {
+    let weight = |header: &String| {
+      overrides.iter().rev()
+        .find(|w| w.category.eq_ignore_ascii_case(header))
+        .map(|w| w.weight)
+        .or_else(|| DEFAULT_WEIGHTS.iter()
+          .find(|(category, _)| category.eq_ignore_ascii_case(header))
+          .map(|(_, weight)| *weight))
+        .unwrap_or(FALLBACK_WEIGHT)
+    };
+
+    let value: f64 = categories.iter()
+      .map(|category| weight(&category.name) * category.hits.iter()
+        .filter(|hit| hit.import != ImportKind::Dynamic)
+        .count() as f64)
+      .sum();
+
+    let verdict = if value >= LIKELY_MALICIOUS {
+      Verdict::LikelyMalicious
+    } else if value >= SUSPICIOUS {
+      Verdict::Suspicious
+    } else {
+      Verdict::Clean
+    };
+
+    Score { value, verdict }
+  }
+}